@@ -0,0 +1,413 @@
+use crate::cli::{ArrangeArgs, UndoArgs};
+use crate::client::{ActionError, NiriClient};
+use crate::layout::{self, LayoutInput, Registry};
+use crate::undo::{self, Snapshot};
+use crate::{plan, select};
+use anyhow::Result;
+use niri_ipc::{Action, Output, Request, WorkspaceReferenceArg};
+use std::collections::HashMap;
+
+/// Arrange the workspaces picked by `args` with the layout it names from `registry`.
+pub fn arrange(client: &mut NiriClient, registry: &Registry, args: &ArrangeArgs) -> Result<()> {
+    let layout = registry.get(args.layout.as_deref().unwrap_or(layout::DEFAULT_LAYOUT))?;
+
+    let windows = client.get_windows()?;
+    let workspaces = client.get_workspaces()?;
+    let outputs = if layout.needs_output_size(args) {
+        client.get_outputs()?
+    } else {
+        HashMap::new()
+    };
+
+    let targets: Vec<_> = if args.all_workspaces {
+        workspaces.iter().collect()
+    } else if let Some(output) = &args.output {
+        let on_output: Vec<_> = workspaces
+            .iter()
+            .filter(|ws| ws.output.as_ref() == Some(output))
+            .collect();
+        if on_output.is_empty() {
+            anyhow::bail!("No workspaces found on output {}", output);
+        }
+        on_output
+    } else {
+        vec![find_workspace(&workspaces, args.workspace.as_ref())?]
+    };
+
+    let mut actions = Vec::new();
+    let mut snapshots = Vec::new();
+    let mut arranged = Vec::new();
+    let mut window_count = 0;
+    let mut num_columns = 0;
+    let mut focused_id = workspaces.iter().find(|ws| ws.is_focused).map(|ws| ws.id);
+
+    let several = targets.len() > 1;
+    let mut skipped = 0;
+
+    for workspace in targets {
+        let plan = match plan_workspace(registry, args, &windows, workspace, &outputs) {
+            Ok(Some(plan)) => plan,
+            Ok(None) => continue,
+            // One workspace that doesn't fit the layout shouldn't keep the others untidy
+            Err(err) if several => {
+                warn!("⚠️  Skipping workspace {}: {:#}", workspace.idx, err);
+                skipped += 1;
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+
+        if focused_id != Some(workspace.id) {
+            debug!("🔀 Switching to workspace {}", workspace.idx);
+            actions.push(Action::FocusWorkspace {
+                reference: WorkspaceReferenceArg::Id(workspace.id),
+            });
+            focused_id = Some(workspace.id);
+        }
+        actions.extend(plan.actions);
+
+        snapshots.push(Snapshot::capture(&windows, workspace));
+        arranged.push(workspace);
+        window_count += plan.window_count;
+        num_columns += plan.num_columns;
+    }
+
+    if arranged.is_empty() {
+        if skipped > 0 {
+            anyhow::bail!("None of the {} workspaces could be arranged", skipped);
+        }
+        info!("No windows found to arrange");
+        return Ok(());
+    }
+
+    actions.extend(focus_back(&windows, &workspaces, &arranged));
+
+    if args.dry_run || args.json {
+        print_plan(&actions, args.json)?;
+        return Ok(());
+    }
+
+    // A replayed run didn't touch any real windows, so there's nothing to undo
+    if !client.is_replay() {
+        undo::save(args.state_dir.as_deref(), &snapshots)?;
+    }
+
+    run_plan(client, actions, args.keep_going)?;
+
+    if arranged.len() == 1 {
+        info!(
+            "✅ Successfully arranged {} windows into {} columns!",
+            window_count, num_columns
+        );
+    } else {
+        info!(
+            "✅ Successfully arranged {} windows on {} workspaces!",
+            window_count,
+            arranged.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Actions arranging one workspace, assuming it is focused.
+pub struct WorkspacePlan {
+    pub actions: Vec<Action>,
+    pub window_count: usize,
+    pub num_columns: usize,
+}
+
+/// Plan the arrangement of `workspace`, or `None` if none of its windows are selected.
+///
+/// `outputs` can be left empty when the layout doesn't
+/// [need the output size](layout::Layout::needs_output_size).
+pub fn plan_workspace(
+    registry: &Registry,
+    args: &ArrangeArgs,
+    windows: &[niri_ipc::Window],
+    workspace: &niri_ipc::Workspace,
+    outputs: &HashMap<String, Output>,
+) -> Result<Option<WorkspacePlan>> {
+    let layout_name = args.layout.as_deref().unwrap_or(layout::DEFAULT_LAYOUT);
+    let layout = registry.get(layout_name)?;
+
+    // Get the workspace windows. Filtered out windows keep their columns, which end up to the
+    // right of the arranged ones.
+    let mut workspace_windows: Vec<_> = windows
+        .iter()
+        .filter(|w| w.workspace_id == Some(workspace.id))
+        .filter(|w| select::is_selected(args, w))
+        .collect();
+    if let Some(key) = args.sort {
+        select::sort(&mut workspace_windows, key);
+    }
+
+    if workspace_windows.is_empty() {
+        debug!("No windows to arrange on workspace {}", workspace.idx);
+        return Ok(None);
+    }
+
+    let window_count = workspace_windows.len();
+    info!(
+        "✅ Found {} windows on workspace {}",
+        window_count, workspace.idx
+    );
+
+    let input = LayoutInput {
+        windows: &workspace_windows,
+        active_window: workspace.active_window_id,
+        output_size: output_size(outputs, workspace),
+        args,
+    };
+    let mut columns = layout.columns(&input)?;
+    layout::check_columns(layout_name, &columns, &input)?;
+    if let Some(widths) = &args.widths {
+        widths.apply(&mut columns)?;
+    }
+    if let Some(heights) = &args.heights {
+        heights.apply(&mut columns)?;
+    }
+    let num_columns = columns.len();
+
+    info!(
+        "📐 Creating {} columns with up to {} windows per column",
+        num_columns,
+        columns.iter().map(|c| c.windows.len()).max().unwrap_or(0)
+    );
+    for (column_idx, column) in columns.iter().enumerate() {
+        debug!(
+            "🏛️  Column {} gets windows {:?}",
+            column_idx, column.windows
+        );
+    }
+
+    let mut actions = Vec::new();
+    for window in workspace_windows.iter().filter(|w| w.is_floating) {
+        actions.push(Action::MoveWindowToTiling {
+            id: Some(window.id),
+        });
+    }
+    // Tiling a floating window shifts the columns around, so don't trust the current ones then
+    let current = if actions.is_empty() {
+        column_ids(windows, workspace.id)
+    } else {
+        Vec::new()
+    };
+    actions.extend(plan::plan(&columns, &current));
+    // Go back to the window the user was working in, so the view doesn't jump to the left
+    actions.push(match workspace.active_window_id {
+        Some(id) if !args.focus_first => Action::FocusWindow { id },
+        _ => Action::FocusColumnFirst {},
+    });
+
+    Ok(Some(WorkspacePlan {
+        actions,
+        window_count,
+        num_columns,
+    }))
+}
+
+/// Actions that take focus back to where the user was after working on `targets`.
+///
+/// Nothing is needed when only the focused workspace was touched. Otherwise the workspaces that
+/// were showing on the other outputs are brought back first, then the focused window (or
+/// workspace, if no window had focus) is focused again.
+fn focus_back(
+    windows: &[niri_ipc::Window],
+    workspaces: &[niri_ipc::Workspace],
+    targets: &[&niri_ipc::Workspace],
+) -> Vec<Action> {
+    let Some(focused) = workspaces.iter().find(|ws| ws.is_focused) else {
+        return Vec::new();
+    };
+    if targets.iter().all(|target| target.id == focused.id) {
+        return Vec::new();
+    }
+
+    let mut actions = Vec::new();
+    let mut outputs = Vec::new();
+
+    for target in targets {
+        if target.output == focused.output || outputs.contains(&&target.output) {
+            continue;
+        }
+        outputs.push(&target.output);
+
+        let shown = workspaces
+            .iter()
+            .find(|ws| ws.is_active && ws.output == target.output);
+        let last_target = targets.iter().rev().find(|t| t.output == target.output);
+        if let Some(shown) = shown.filter(|shown| Some(shown.id) != last_target.map(|t| t.id)) {
+            actions.push(Action::FocusWorkspace {
+                reference: WorkspaceReferenceArg::Id(shown.id),
+            });
+        }
+    }
+
+    actions.push(match windows.iter().find(|w| w.is_focused) {
+        Some(window) => Action::FocusWindow { id: window.id },
+        None => Action::FocusWorkspace {
+            reference: WorkspaceReferenceArg::Id(focused.id),
+        },
+    });
+
+    actions
+}
+
+/// Window ids of the current columns of a workspace.
+fn column_ids(windows: &[niri_ipc::Window], workspace_id: u64) -> Vec<Vec<u64>> {
+    plan::current_columns(windows, workspace_id)
+        .into_iter()
+        .map(|column| column.into_iter().map(|w| w.id).collect())
+        .collect()
+}
+
+/// Logical size of the output showing `workspace`.
+fn output_size(
+    outputs: &HashMap<String, Output>,
+    workspace: &niri_ipc::Workspace,
+) -> Option<(f64, f64)> {
+    let logical = outputs.get(workspace.output.as_ref()?)?.logical?;
+    Some((logical.width as f64, logical.height as f64))
+}
+
+pub fn undo(client: &mut NiriClient, args: &UndoArgs) -> Result<()> {
+    let Some(snapshots) = undo::load(args.state_dir.as_deref())? else {
+        info!("Nothing to undo");
+        return Ok(());
+    };
+
+    let windows = client.get_windows()?;
+    let workspaces = client.get_workspaces()?;
+
+    let mut actions = Vec::new();
+    let mut restored = Vec::new();
+
+    for mut snapshot in snapshots {
+        let Some(workspace) = workspaces.iter().find(|ws| ws.id == snapshot.workspace_id) else {
+            warn!(
+                "⚠️  Workspace {} no longer exists, skipping it",
+                snapshot.workspace_id
+            );
+            continue;
+        };
+        snapshot.retain_present(&windows);
+
+        actions.push(Action::FocusWorkspace {
+            reference: WorkspaceReferenceArg::Id(snapshot.workspace_id),
+        });
+        let current = column_ids(&windows, snapshot.workspace_id);
+        actions.extend(plan::plan(&snapshot.columns, &current));
+        for &id in &snapshot.floating {
+            if windows.iter().any(|w| w.id == id && !w.is_floating) {
+                actions.push(Action::MoveWindowToFloating { id: Some(id) });
+            }
+        }
+        actions.push(match snapshot.focused_window {
+            Some(id) => Action::FocusWindow { id },
+            None => Action::FocusColumnFirst {},
+        });
+
+        info!(
+            "↩️  Restoring {} columns on workspace {}",
+            snapshot.columns.len(),
+            workspace.idx
+        );
+        restored.push(workspace);
+    }
+
+    actions.extend(focus_back(&windows, &workspaces, &restored));
+
+    if args.dry_run || args.json {
+        print_plan(&actions, args.json)?;
+        return Ok(());
+    }
+
+    run_plan(client, actions, args.keep_going)?;
+    if !client.is_replay() {
+        undo::remove(args.state_dir.as_deref())?;
+    }
+
+    Ok(())
+}
+
+/// Send the planned actions in order.
+///
+/// Stops at the first action niri rejects, unless `keep_going` is set, in which case every
+/// failure is reported once all actions have been sent.
+pub fn run_plan(client: &mut NiriClient, actions: Vec<Action>, keep_going: bool) -> Result<()> {
+    let total = actions.len();
+    let mut failures = Vec::new();
+
+    for (step, action) in actions.into_iter().enumerate() {
+        let description = plan::describe(&action);
+        debug!("   ▶️  {}", description);
+
+        match client.action(action) {
+            Ok(()) => {}
+            Err(err) if keep_going && err.is::<ActionError>() => {
+                warn!("   ⚠️  Step {} ({}) failed: {}", step + 1, description, err);
+                failures.push(step + 1);
+            }
+            Err(err) => {
+                return Err(err.context(format!(
+                    "Step {} of {} ({}) failed",
+                    step + 1,
+                    total,
+                    description
+                )))
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        anyhow::bail!(
+            "{} of {} actions failed (steps {:?})",
+            failures.len(),
+            total,
+            failures
+        );
+    }
+
+    Ok(())
+}
+
+/// Print the actions as a numbered list, or as niri IPC requests with `json`.
+pub fn print_plan(actions: &[Action], json: bool) -> Result<()> {
+    for (step, action) in actions.iter().enumerate() {
+        if json {
+            println!(
+                "{}",
+                serde_json::to_string(&Request::Action(action.clone()))?
+            );
+        } else {
+            println!("{:>3}. {}", step + 1, plan::describe(action));
+        }
+    }
+
+    Ok(())
+}
+
+/// Find the workspace `reference` points to, or the focused one if it's `None`.
+pub fn find_workspace<'a>(
+    workspaces: &'a [niri_ipc::Workspace],
+    reference: Option<&WorkspaceReferenceArg>,
+) -> Result<&'a niri_ipc::Workspace> {
+    let focused = workspaces.iter().find(|ws| ws.is_focused);
+
+    let Some(reference) = reference else {
+        return focused.ok_or_else(|| anyhow::anyhow!("No focused workspace found"));
+    };
+
+    workspaces
+        .iter()
+        .find(|ws| match reference {
+            WorkspaceReferenceArg::Id(id) => ws.id == *id,
+            // Indices are per output, so resolve them like niri does: on the focused output
+            WorkspaceReferenceArg::Index(idx) => {
+                ws.idx == *idx && ws.output == focused.and_then(|f| f.output.clone())
+            }
+            WorkspaceReferenceArg::Name(name) => ws.name.as_deref() == Some(name.as_str()),
+        })
+        .ok_or_else(|| anyhow::anyhow!("Workspace {:?} not found", reference))
+}
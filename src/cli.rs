@@ -0,0 +1,294 @@
+use crate::layout::{AspectRatio, Heights, Shape, Widths};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgAction, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use niri_ipc::WorkspaceReferenceArg;
+use regex::Regex;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Arrange the windows of a niri workspace into compact columns"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Arrange options used when no subcommand is given
+    #[command(flatten)]
+    pub arrange: PresetArgs,
+
+    /// Print more details about what is being done (can be repeated)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Config file to read presets from [default: $XDG_CONFIG_HOME/niri-compact/config.toml]
+    #[arg(long, value_name = "FILE", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Write every request sent to niri and its reply to FILE as JSON lines
+    #[arg(long, value_name = "FILE", global = true)]
+    pub record: Option<PathBuf>,
+
+    /// Take niri's replies from a transcript written by --record instead of asking niri
+    #[arg(long, value_name = "FILE", global = true)]
+    pub replay: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Parse the command line, rejecting arrange options given before a subcommand.
+    ///
+    /// Those would otherwise be ignored silently. clap's `args_conflicts_with_subcommands`
+    /// can't be used for this because it also rejects global options like `--verbose`.
+    pub fn parse_args() -> Self {
+        let mut command = Cli::command();
+        let matches = command.get_matches_mut();
+
+        if let Some(subcommand) = matches.subcommand_name() {
+            let arrange_args = PresetArgs::augment_args(clap::Command::new("arrange"));
+            let misplaced = arrange_args.get_arguments().find(|arg| {
+                matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+            });
+
+            if let Some(arg) = misplaced {
+                command
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        format!(
+                            "--{} must come after the '{}' subcommand",
+                            arg.get_long().unwrap_or_default(),
+                            subcommand
+                        ),
+                    )
+                    .exit();
+            }
+        }
+
+        Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Arrange the windows of a workspace (the default)
+    Arrange(PresetArgs),
+    /// Print the actions an arrange would send, without sending them
+    Plan(PresetArgs),
+    /// Restore the workspace as it was before the last arrange
+    Undo(UndoArgs),
+    /// Keep running and re-arrange workspaces whenever windows open or close
+    Daemon(DaemonArgs),
+}
+
+/// Arrange options as given on the command line: a preset and the options overriding it.
+///
+/// Presets come from the config file, so the binary resolves them into [`ArrangeArgs`] before
+/// arranging.
+#[derive(Args, Debug, Clone, Default)]
+#[command(about = None, long_about = None)]
+pub struct PresetArgs {
+    /// Named preset from the config file to take the options from
+    #[arg(short, long, value_name = "NAME")]
+    pub preset: Option<String>,
+
+    #[command(flatten)]
+    pub arrange: ArrangeArgs,
+}
+
+/// Options of an arrange, with any preset already applied.
+#[derive(Args, Debug, Clone, Default)]
+pub struct ArrangeArgs {
+    /// Layout algorithm used to arrange the windows: grid, master-stack or a custom one
+    /// [default: grid]
+    #[arg(short, long, value_name = "NAME")]
+    pub layout: Option<String>,
+
+    /// Number of columns to create instead of picking one from the window count
+    #[arg(short, long, value_name = "N")]
+    pub columns: Option<usize>,
+
+    /// Maximum number of windows stacked in one column
+    #[arg(short = 'r', long, value_name = "M")]
+    pub max_rows: Option<usize>,
+
+    /// Pick the column count whose cells on the output come closest to this aspect ratio, e.g.
+    /// 16:9 or 1.78
+    ///
+    /// Ignored when --columns or --max-rows is given, and by the master-stack and script layouts.
+    #[arg(short = 'a', long, value_name = "RATIO")]
+    pub cell_aspect: Option<AspectRatio>,
+
+    /// Exact number of windows per column, e.g. 3,2,2 or 1|2x3, with optional widths (3@50)
+    ///
+    /// Replaces the column count picked by --columns, --max-rows and --cell-aspect.
+    #[arg(long, value_name = "SHAPE", conflicts_with_all = ["columns", "max_rows", "cell_aspect"])]
+    pub shape: Option<Shape>,
+
+    /// Whether windows fill the columns top to bottom or the rows left to right [default: column]
+    #[arg(short, long, value_enum)]
+    pub fill: Option<FillOrder>,
+
+    /// Column widths left to right, as percentages (50,25,25) or ratios (2:1:1)
+    #[arg(long, value_name = "WIDTHS")]
+    pub widths: Option<Widths>,
+
+    /// Window heights in percent from the top of each column, e.g. 70 or 50,30
+    ///
+    /// Windows without a height of their own share what's left.
+    #[arg(long, value_name = "HEIGHTS")]
+    pub heights: Option<Heights>,
+
+    /// Width of the master column in the master-stack layout, in percent [default: 60]
+    #[arg(long, value_name = "PERCENT")]
+    pub master_width: Option<f64>,
+
+    /// Workspace to arrange, by index, name or id:N (defaults to the focused workspace)
+    ///
+    /// Indices refer to the focused output. Focus returns to where it was afterwards.
+    #[arg(short, long, value_name = "REF", value_parser = workspace_reference)]
+    pub workspace: Option<WorkspaceReferenceArg>,
+
+    /// Arrange every workspace on this output
+    #[arg(long, value_name = "NAME", conflicts_with = "workspace")]
+    pub output: Option<String>,
+
+    /// Arrange every workspace on every output
+    #[arg(long, conflicts_with_all = ["workspace", "output"])]
+    pub all_workspaces: bool,
+
+    /// Tile floating windows into the arrangement instead of leaving them alone
+    #[arg(long, overrides_with = "no_include_floating")]
+    pub include_floating: bool,
+
+    /// Leave floating windows alone even if the preset tiles them in
+    #[arg(long, overrides_with = "include_floating")]
+    pub no_include_floating: bool,
+
+    /// Only arrange windows whose app id matches this regex (can be repeated)
+    #[arg(long, value_name = "REGEX")]
+    pub include_app: Vec<Regex>,
+
+    /// Leave windows whose app id matches this regex alone (can be repeated)
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_app: Vec<Regex>,
+
+    /// Only arrange windows whose title matches this regex (can be repeated)
+    #[arg(long, value_name = "REGEX")]
+    pub include_title: Vec<Regex>,
+
+    /// Leave windows whose title matches this regex alone (can be repeated)
+    #[arg(long, value_name = "REGEX")]
+    pub exclude_title: Vec<Regex>,
+
+    /// Order in which windows are placed into the columns [default: as listed by niri]
+    #[arg(short, long, value_enum, value_name = "KEY")]
+    pub sort: Option<SortKey>,
+
+    /// Finish on the first column instead of the window that was focused before
+    #[arg(long, overrides_with = "no_focus_first")]
+    pub focus_first: bool,
+
+    /// Finish on the window that was focused before even if the preset says --focus-first
+    #[arg(long, overrides_with = "focus_first")]
+    pub no_focus_first: bool,
+
+    /// Print the planned actions instead of sending them to niri
+    #[arg(short = 'n', long)]
+    pub dry_run: bool,
+
+    /// Print the plan as niri IPC requests, one JSON object per line, instead of sending it
+    ///
+    /// Implies --dry-run.
+    #[arg(long)]
+    pub json: bool,
+
+    /// Keep sending the remaining actions when niri rejects one
+    #[arg(long)]
+    pub keep_going: bool,
+
+    /// Directory to keep the undo state in [default: $XDG_RUNTIME_DIR/niri-compact]
+    #[arg(long, value_name = "DIR")]
+    pub state_dir: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FillOrder {
+    /// Fill the first column completely, then the next one
+    #[default]
+    Column,
+    /// Spread windows over the columns left to right, like reading a page
+    Row,
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortKey {
+    /// Application id, alphabetically
+    AppId,
+    /// Window title, alphabetically
+    Title,
+    /// Process id of the client
+    Pid,
+    /// niri window id, roughly the order windows were opened in
+    Id,
+    /// Current position on screen, left to right and top to bottom
+    Position,
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct UndoArgs {
+    /// Print the planned actions instead of sending them to niri
+    #[arg(short = 'n', long)]
+    pub dry_run: bool,
+
+    /// Print the plan as niri IPC requests, one JSON object per line, instead of sending it
+    ///
+    /// Implies --dry-run.
+    #[arg(long)]
+    pub json: bool,
+
+    /// Keep sending the remaining actions when niri rejects one
+    #[arg(long)]
+    pub keep_going: bool,
+
+    /// Directory to keep the undo state in [default: $XDG_RUNTIME_DIR/niri-compact]
+    #[arg(long, value_name = "DIR")]
+    pub state_dir: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DaemonArgs {
+    /// Workspace to keep compact, by index, name or id:N (can be repeated)
+    #[arg(
+        long = "watch",
+        value_name = "REF",
+        required = true,
+        value_parser = workspace_reference
+    )]
+    pub watch: Vec<WorkspaceReferenceArg>,
+
+    /// Wait this long after the last window change before arranging
+    #[arg(long, value_name = "MS", default_value_t = 300)]
+    pub debounce: u64,
+
+    #[command(flatten)]
+    pub arrange: PresetArgs,
+}
+
+/// Parse a workspace reference like niri does, plus `id:N` to pick a workspace by its id.
+fn workspace_reference(s: &str) -> Result<WorkspaceReferenceArg, String> {
+    match s.strip_prefix("id:") {
+        Some(id) => id
+            .parse()
+            .map(WorkspaceReferenceArg::Id)
+            .map_err(|_| format!("invalid workspace id {:?}", id)),
+        None => s.parse().map_err(str::to_string),
+    }
+}
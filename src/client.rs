@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use niri_ipc::{Action, Event, Reply, Request, Response};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+/// niri replied to an action with an error, e.g. because the window it refers to is gone.
+#[derive(Debug)]
+pub struct ActionError {
+    pub action: Action,
+    pub message: String,
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "niri rejected {:?}: {}", self.action, self.message)
+    }
+}
+
+impl std::error::Error for ActionError {}
+
+/// One request and the reply niri gave to it, a line of a transcript.
+#[derive(Serialize, Deserialize)]
+struct Exchange {
+    request: Request,
+    reply: Reply,
+}
+
+enum Backend {
+    Socket {
+        reader: BufReader<UnixStream>,
+        writer: UnixStream,
+    },
+    /// Replies from a recorded transcript, in the order they were received.
+    Replay(VecDeque<Exchange>),
+}
+
+pub struct NiriClient {
+    backend: Backend,
+    recorder: Option<File>,
+}
+
+impl NiriClient {
+    pub fn new(socket_path: &str) -> Result<Self> {
+        let stream = UnixStream::connect(socket_path)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let writer = stream;
+
+        Ok(NiriClient {
+            backend: Backend::Socket { reader, writer },
+            recorder: None,
+        })
+    }
+
+    /// Answer requests from a transcript written by [`NiriClient::record`] instead of niri.
+    ///
+    /// The requests have to come in the same order as when the transcript was recorded, so
+    /// replaying it with the same options reproduces the same run.
+    pub fn replay(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let exchanges = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("Failed to parse {}:{}", path.display(), number + 1))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(NiriClient {
+            backend: Backend::Replay(exchanges),
+            recorder: None,
+        })
+    }
+
+    /// Check that a replay sent every request of the transcript.
+    ///
+    /// A run that stops short of what was recorded planned something different, even if every
+    /// request it sent matched.
+    pub fn finish_replay(&self) -> Result<()> {
+        if let Backend::Replay(exchanges) = &self.backend {
+            if let Some(exchange) = exchanges.front() {
+                anyhow::bail!(
+                    "Replay ended with {} requests of the transcript left, starting with {:?}",
+                    exchanges.len(),
+                    exchange.request
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self.backend, Backend::Replay(_))
+    }
+
+    /// Write every request and reply from now on to `path`, one JSON object per line.
+    pub fn record(&mut self, path: &Path) -> Result<()> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        self.recorder = Some(file);
+        Ok(())
+    }
+
+    /// Send an action, turning an error reply into an [`ActionError`].
+    pub fn action(&mut self, action: Action) -> Result<()> {
+        match self.execute(Request::Action(action.clone()))? {
+            Ok(_) => Ok(()),
+            Err(message) => Err(ActionError { action, message }.into()),
+        }
+    }
+
+    pub fn execute(&mut self, request: Request) -> Result<Reply> {
+        let reply = match &mut self.backend {
+            Backend::Socket { reader, writer } => {
+                writeln!(writer, "{}", serde_json::to_string(&request)?)?;
+
+                let mut response_line = String::new();
+                reader.read_line(&mut response_line)?;
+
+                serde_json::from_str(&response_line)?
+            }
+            Backend::Replay(exchanges) => {
+                let Some(exchange) = exchanges.pop_front() else {
+                    anyhow::bail!("Transcript ended before {:?}", request);
+                };
+                if serde_json::to_value(&exchange.request)? != serde_json::to_value(&request)? {
+                    anyhow::bail!(
+                        "Replay diverged from the transcript: sent {:?}, recorded {:?}",
+                        request,
+                        exchange.request
+                    );
+                }
+                exchange.reply
+            }
+        };
+
+        if let Some(recorder) = &mut self.recorder {
+            let exchange = Exchange { request, reply };
+            writeln!(recorder, "{}", serde_json::to_string(&exchange)?)?;
+            return Ok(exchange.reply);
+        }
+
+        Ok(reply)
+    }
+
+    pub fn get_windows(&mut self) -> Result<Vec<niri_ipc::Window>> {
+        match self.execute(Request::Windows)? {
+            Ok(Response::Windows(windows)) => Ok(windows),
+            _ => Err(anyhow::anyhow!("Failed to get windows")),
+        }
+    }
+
+    pub fn get_outputs(&mut self) -> Result<HashMap<String, niri_ipc::Output>> {
+        match self.execute(Request::Outputs)? {
+            Ok(Response::Outputs(outputs)) => Ok(outputs),
+            _ => Err(anyhow::anyhow!("Failed to get outputs")),
+        }
+    }
+
+    /// Switch this connection over to the event stream.
+    ///
+    /// Afterwards the connection can only be used to read events.
+    pub fn subscribe(&mut self) -> Result<()> {
+        match self.execute(Request::EventStream)? {
+            Ok(Response::Handled) => Ok(()),
+            _ => Err(anyhow::anyhow!("Failed to subscribe to the event stream")),
+        }
+    }
+
+    /// Block until the next event arrives on a subscribed connection.
+    pub fn read_event(&mut self) -> Result<Event> {
+        let Backend::Socket { reader, .. } = &mut self.backend else {
+            anyhow::bail!("Transcripts don't contain events");
+        };
+
+        let mut event_line = String::new();
+        if reader.read_line(&mut event_line)? == 0 {
+            anyhow::bail!("niri closed the event stream");
+        }
+
+        Ok(serde_json::from_str(&event_line)?)
+    }
+
+    pub fn get_workspaces(&mut self) -> Result<Vec<niri_ipc::Workspace>> {
+        match self.execute(Request::Workspaces)? {
+            Ok(Response::Workspaces(workspaces)) => Ok(workspaces),
+            _ => Err(anyhow::anyhow!("Failed to get workspaces")),
+        }
+    }
+}
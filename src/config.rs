@@ -0,0 +1,164 @@
+use crate::cli::{ArrangeArgs, FillOrder, SortKey};
+use crate::layout::{AspectRatio, Heights, Shape, Widths};
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Preset used when `--preset` isn't given.
+///
+/// If the config file doesn't define it, the built-in default (a square grid of every tiled
+/// window) is used.
+pub const DEFAULT_PRESET: &str = "default";
+
+/// Contents of `config.toml`.
+///
+/// ```toml
+/// [presets.coding]
+/// columns = 2
+/// exclude-app = ["firefox", "spotify"]
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub presets: HashMap<String, Preset>,
+}
+
+/// Named set of arrange options.
+///
+/// Options given on the command line take precedence over the preset, including the filter
+/// lists, which replace the preset's lists rather than extending them.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Preset {
+    pub layout: Option<String>,
+    pub columns: Option<usize>,
+    pub max_rows: Option<usize>,
+    pub cell_aspect: Option<AspectRatio>,
+    pub shape: Option<Shape>,
+    pub fill: Option<FillOrder>,
+    pub widths: Option<Widths>,
+    pub heights: Option<Heights>,
+    pub master_width: Option<f64>,
+    pub include_floating: Option<bool>,
+    #[serde(deserialize_with = "regexes")]
+    pub include_app: Vec<Regex>,
+    #[serde(deserialize_with = "regexes")]
+    pub exclude_app: Vec<Regex>,
+    #[serde(deserialize_with = "regexes")]
+    pub include_title: Vec<Regex>,
+    #[serde(deserialize_with = "regexes")]
+    pub exclude_title: Vec<Regex>,
+    pub sort: Option<SortKey>,
+    pub focus_first: Option<bool>,
+}
+
+impl Config {
+    /// Load the config from `path`, or from the default location if it's `None`.
+    ///
+    /// A missing file at the default location is the same as an empty config.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Config::default()),
+            },
+        };
+
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn preset(&self, name: Option<&str>) -> Result<Preset> {
+        match name {
+            None => Ok(self
+                .presets
+                .get(DEFAULT_PRESET)
+                .cloned()
+                .unwrap_or_default()),
+            Some(name) => self.presets.get(name).cloned().ok_or_else(|| {
+                let mut known: Vec<_> = self.presets.keys().map(String::as_str).collect();
+                known.sort_unstable();
+                anyhow::anyhow!("Unknown preset {:?} (known presets: {:?})", name, known)
+            }),
+        }
+    }
+}
+
+impl Preset {
+    /// Fill in every option that wasn't given on the command line.
+    pub fn apply(self, args: ArrangeArgs) -> ArrangeArgs {
+        let or_preset = |cli: Vec<Regex>, preset: Vec<Regex>| {
+            if cli.is_empty() {
+                preset
+            } else {
+                cli
+            }
+        };
+
+        // `--foo` and `--no-foo` both beat the preset
+        let flag = |cli: bool, cli_off: bool, preset: Option<bool>| {
+            cli || (!cli_off && preset.unwrap_or(false))
+        };
+
+        // --shape and the options picking a column count replace each other, the command line
+        // taking precedence over the preset
+        let cli_shape = args.shape.is_some();
+        let cli_count =
+            args.columns.is_some() || args.max_rows.is_some() || args.cell_aspect.is_some();
+
+        ArrangeArgs {
+            layout: args.layout.or(self.layout),
+            columns: args.columns.or(self.columns.filter(|_| !cli_shape)),
+            max_rows: args.max_rows.or(self.max_rows.filter(|_| !cli_shape)),
+            cell_aspect: args.cell_aspect.or(self.cell_aspect.filter(|_| !cli_shape)),
+            shape: args.shape.or(self.shape.filter(|_| !cli_count)),
+            fill: args.fill.or(self.fill),
+            widths: args.widths.or(self.widths),
+            heights: args.heights.or(self.heights),
+            master_width: args.master_width.or(self.master_width),
+            include_floating: flag(
+                args.include_floating,
+                args.no_include_floating,
+                self.include_floating,
+            ),
+            include_app: or_preset(args.include_app, self.include_app),
+            exclude_app: or_preset(args.exclude_app, self.exclude_app),
+            include_title: or_preset(args.include_title, self.include_title),
+            exclude_title: or_preset(args.exclude_title, self.exclude_title),
+            sort: args.sort.or(self.sort),
+            focus_first: flag(args.focus_first, args.no_focus_first, self.focus_first),
+            ..args
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/niri-compact/config.toml`, falling back to `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join("niri-compact").join("config.toml"))
+}
+
+/// Directory with layout scripts, next to the config file.
+pub fn layouts_dir(config_path: Option<&Path>) -> Option<PathBuf> {
+    let config_path = match config_path {
+        Some(path) => path.to_path_buf(),
+        None => default_path()?,
+    };
+
+    Some(config_path.parent()?.join("layouts"))
+}
+
+fn regexes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Regex>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pattern| Regex::new(pattern).map_err(serde::de::Error::custom))
+        .collect()
+}
@@ -0,0 +1,147 @@
+use crate::cli::ArrangeArgs;
+use crate::client::NiriClient;
+use crate::layout::{Registry, DEFAULT_LAYOUT};
+use anyhow::Result;
+use niri_ipc::{Event, WorkspaceReferenceArg};
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Where a window lives, as far as arranging is concerned.
+///
+/// `WindowOpenedOrChanged` also fires for title changes, so only a change of this tells us the
+/// workspace layout needs another pass.
+type Placement = (Option<u64>, bool);
+
+/// Follow the niri event stream and re-arrange watched workspaces after windows open, close or
+/// move between workspaces.
+///
+/// Bursts of events are debounced into a single arrange. Workspaces that change while they're
+/// in the background are arranged once they get focused, so the daemon never steals focus.
+/// `args` are the options of every arrange, whose target workspace the daemon picks itself.
+pub fn run(
+    client: &mut NiriClient,
+    socket_path: &str,
+    registry: &Registry,
+    watch: &[WorkspaceReferenceArg],
+    debounce: Duration,
+    args: &ArrangeArgs,
+) -> Result<()> {
+    // Catch a typo now rather than on every window change
+    registry.get(args.layout.as_deref().unwrap_or(DEFAULT_LAYOUT))?;
+
+    let mut events = NiriClient::new(socket_path)?;
+    events.subscribe()?;
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || loop {
+        let event = events.read_event();
+        let failed = event.is_err();
+        if sender.send(event).is_err() || failed {
+            break;
+        }
+    });
+
+    let mut placements: HashMap<u64, Placement> = HashMap::new();
+    // Workspaces with window changes that are still settling
+    let mut dirty: HashSet<u64> = HashSet::new();
+    // Watched background workspaces to arrange once they get focused
+    let mut pending: HashSet<u64> = HashSet::new();
+
+    info!("👀 Watching {} workspaces for window changes", watch.len());
+
+    // When the settling workspaces get arranged. Only set when `dirty` gains a workspace, so
+    // title or focus changes don't keep pushing it back.
+    let mut deadline: Option<Instant> = None;
+
+    loop {
+        let event = match deadline {
+            None => receiver.recv()?,
+            Some(at) => match receiver.recv_timeout(at.saturating_duration_since(Instant::now())) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => {
+                    deadline = None;
+                    pending.extend(dirty.drain());
+                    arrange_pending(client, registry, watch, args, &mut pending)?;
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    anyhow::bail!("Event stream thread stopped")
+                }
+            },
+        };
+
+        let mut changed = Vec::new();
+        match event? {
+            Event::WindowsChanged { windows } => {
+                placements = windows
+                    .into_iter()
+                    .map(|w| (w.id, (w.workspace_id, w.is_floating)))
+                    .collect();
+            }
+            Event::WindowOpenedOrChanged { window } => {
+                let placement = (window.workspace_id, window.is_floating);
+                let previous = placements.insert(window.id, placement);
+                if previous != Some(placement) {
+                    changed.extend(previous.and_then(|(workspace_id, _)| workspace_id));
+                    changed.extend(window.workspace_id);
+                }
+            }
+            Event::WindowClosed { id } => {
+                if let Some((workspace_id, _)) = placements.remove(&id) {
+                    changed.extend(workspace_id);
+                }
+            }
+            Event::WorkspaceActivated { id, focused: true } if pending.contains(&id) => {
+                arrange_pending(client, registry, watch, args, &mut pending)?;
+            }
+            _ => {}
+        }
+
+        for workspace_id in changed {
+            if dirty.insert(workspace_id) {
+                deadline = Some(Instant::now() + debounce);
+            }
+        }
+    }
+}
+
+/// Arrange the pending workspace if it is watched and focused.
+///
+/// Unwatched workspaces are forgotten, background ones stay pending until they get focused.
+fn arrange_pending(
+    client: &mut NiriClient,
+    registry: &Registry,
+    watch: &[WorkspaceReferenceArg],
+    args: &ArrangeArgs,
+    pending: &mut HashSet<u64>,
+) -> Result<()> {
+    let workspaces = client.get_workspaces()?;
+
+    let watched: HashSet<u64> = watch
+        .iter()
+        .filter_map(|reference| crate::find_workspace(&workspaces, Some(reference)).ok())
+        .map(|ws| ws.id)
+        .collect();
+    pending.retain(|id| watched.contains(id));
+
+    let Some(focused) = workspaces.iter().find(|ws| ws.is_focused) else {
+        return Ok(());
+    };
+    if !pending.remove(&focused.id) {
+        return Ok(());
+    }
+
+    let arrange_args = ArrangeArgs {
+        workspace: Some(WorkspaceReferenceArg::Id(focused.id)),
+        output: None,
+        all_workspaces: false,
+        ..args.clone()
+    };
+    // A window closing halfway through shouldn't take the daemon down with it
+    if let Err(err) = crate::arrange(client, registry, &arrange_args) {
+        error!("❌ Failed to arrange workspace {}: {:#}", focused.idx, err);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,744 @@
+use crate::cli::{ArrangeArgs, FillOrder};
+use anyhow::Result;
+use niri_ipc::{ColumnDisplay, SizeChange, Window};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// One column of the target layout.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Column {
+    /// Windows in the column, top to bottom.
+    pub windows: Vec<u64>,
+    /// Column width to apply once the column is built.
+    pub width: SizeChange,
+    /// Heights of the windows from the top, empty to keep niri's automatic heights.
+    ///
+    /// The bottom window takes whatever height is left, so at most `windows.len() - 1` of these
+    /// are applied.
+    #[serde(default)]
+    pub heights: Vec<SizeChange>,
+    /// Whether the windows are stacked or shown as tabs.
+    #[serde(default = "normal_display")]
+    pub display: ColumnDisplay,
+}
+
+impl Column {
+    /// Stacked column of `windows` with niri's automatic heights.
+    pub fn new(windows: Vec<u64>, width: SizeChange) -> Self {
+        Column {
+            windows,
+            width,
+            heights: Vec::new(),
+            display: ColumnDisplay::Normal,
+        }
+    }
+}
+
+fn normal_display() -> ColumnDisplay {
+    ColumnDisplay::Normal
+}
+
+/// Layout used when `--layout` isn't given.
+pub const DEFAULT_LAYOUT: &str = "grid";
+
+/// What a layout gets to base the columns on.
+pub struct LayoutInput<'a> {
+    /// Selected windows of the workspace, in the order they should be arranged in.
+    pub windows: &'a [&'a Window],
+    /// Active window of the workspace, which is the focused one unless it is in the background.
+    pub active_window: Option<u64>,
+    /// Logical size of the output showing the workspace, if niri knows it and the layout
+    /// [needs it](Layout::needs_output_size).
+    pub output_size: Option<(f64, f64)>,
+    pub args: &'a ArrangeArgs,
+}
+
+impl LayoutInput<'_> {
+    pub fn window_ids(&self) -> Vec<u64> {
+        self.windows.iter().map(|w| w.id).collect()
+    }
+}
+
+/// Algorithm deciding the target columns of a workspace.
+pub trait Layout {
+    /// Columns from left to right. Every window of `input` has to be in exactly one of them.
+    fn columns(&self, input: &LayoutInput) -> Result<Vec<Column>>;
+
+    /// Whether `columns` looks at [`LayoutInput::output_size`] with these options.
+    ///
+    /// The outputs are only asked from niri when it does, otherwise `output_size` is `None`.
+    fn needs_output_size(&self, _args: &ArrangeArgs) -> bool {
+        false
+    }
+}
+
+/// Layouts by the name they're chosen with in `--layout` and presets.
+pub struct Registry {
+    layouts: BTreeMap<String, Box<dyn Layout>>,
+}
+
+impl Default for Registry {
+    /// Registry with the built-in layouts.
+    fn default() -> Self {
+        let mut registry = Registry {
+            layouts: BTreeMap::new(),
+        };
+        registry.register("grid", Grid);
+        registry.register("master-stack", MasterStack);
+        registry
+    }
+}
+
+impl Registry {
+    /// Add a layout, replacing any layout of the same name.
+    pub fn register(&mut self, name: impl Into<String>, layout: impl Layout + 'static) {
+        self.layouts.insert(name.into(), Box::new(layout));
+    }
+
+    pub fn get(&self, name: &str) -> Result<&dyn Layout> {
+        self.layouts.get(name).map(Box::as_ref).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown layout {:?} (known layouts: {:?})",
+                name,
+                self.names().collect::<Vec<_>>()
+            )
+        })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.layouts.keys().map(String::as_str)
+    }
+}
+
+/// Roughly square grid of equally sized columns.
+pub struct Grid;
+
+impl Layout for Grid {
+    fn columns(&self, input: &LayoutInput) -> Result<Vec<Column>> {
+        let args = input.args;
+        if let Some(shape) = &args.shape {
+            return shape.apply(&input.window_ids(), args.fill.unwrap_or_default());
+        }
+
+        let window_count = input.windows.len();
+        let num_columns = match (args.cell_aspect, args.columns, args.max_rows) {
+            (Some(aspect), None, None) => {
+                let (width, height) = input
+                    .output_size
+                    .ok_or_else(|| anyhow::anyhow!("--cell-aspect needs the size of the output"))?;
+                fit_columns(window_count, width, height, aspect)
+            }
+            _ => grid_columns(window_count, args.columns, args.max_rows)?,
+        };
+
+        Ok(grid(
+            &input.window_ids(),
+            num_columns,
+            args.fill.unwrap_or_default(),
+        ))
+    }
+
+    fn needs_output_size(&self, args: &ArrangeArgs) -> bool {
+        args.shape.is_none()
+            && args.cell_aspect.is_some()
+            && args.columns.is_none()
+            && args.max_rows.is_none()
+    }
+}
+
+/// The active window in a wide column, the others stacked next to it.
+pub struct MasterStack;
+
+impl Layout for MasterStack {
+    fn columns(&self, input: &LayoutInput) -> Result<Vec<Column>> {
+        let args = input.args;
+        if args.shape.is_some() {
+            anyhow::bail!("--shape only works with the grid layout");
+        }
+
+        let window_ids = input.window_ids();
+        let master = match input.active_window.filter(|id| window_ids.contains(id)) {
+            Some(id) => id,
+            None => *window_ids
+                .first()
+                .ok_or_else(|| anyhow::anyhow!("There are no windows to arrange"))?,
+        };
+
+        master_stack(
+            &window_ids,
+            master,
+            args.master_width.unwrap_or(DEFAULT_MASTER_WIDTH),
+            args.columns,
+            args.max_rows,
+            args.fill.unwrap_or_default(),
+        )
+    }
+}
+
+/// Check that a layout placed every window of `input` exactly once.
+pub fn check_columns(name: &str, columns: &[Column], input: &LayoutInput) -> Result<()> {
+    let mut placed: Vec<u64> = columns.iter().flat_map(|c| c.windows.clone()).collect();
+    if columns.iter().any(|column| column.windows.is_empty()) {
+        anyhow::bail!("Layout {:?} returned an empty column", name);
+    }
+
+    let mut expected = input.window_ids();
+    placed.sort_unstable();
+    expected.sort_unstable();
+    if placed != expected {
+        anyhow::bail!(
+            "Layout {:?} has to place windows {:?} once each, but placed {:?}",
+            name,
+            expected,
+            placed
+        );
+    }
+
+    Ok(())
+}
+
+/// Per-column widths in percent, left to right.
+///
+/// Parsed from percentages that add up to 100 (`50,25,25`) or from ratios (`2:1:1`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Widths(pub Vec<f64>);
+
+impl FromStr for Widths {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (separator, ratios) = if s.contains(':') {
+            (':', true)
+        } else {
+            (',', false)
+        };
+
+        let values = s
+            .split(separator)
+            .map(|value| {
+                positive_number(value)
+                    .ok_or_else(|| format!("invalid width {:?}, expected a positive number", value))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let total: f64 = values.iter().sum();
+
+        if ratios {
+            return Ok(Widths(values.iter().map(|v| v * 100.0 / total).collect()));
+        }
+        // Allow for rounding, e.g. 33.3,33.3,33.3
+        if (total - 100.0).abs() > 0.5 {
+            return Err(format!("widths must add up to 100%, got {}%", total));
+        }
+
+        Ok(Widths(values))
+    }
+}
+
+impl<'de> Deserialize<'de> for Widths {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_from_str(deserializer)
+    }
+}
+
+impl Widths {
+    /// Override the widths the layout picked, one value per column.
+    pub fn apply(&self, columns: &mut [Column]) -> Result<()> {
+        if self.0.len() != columns.len() {
+            anyhow::bail!(
+                "{} widths given for {} columns",
+                self.0.len(),
+                columns.len()
+            );
+        }
+
+        for (column, &width) in columns.iter_mut().zip(&self.0) {
+            column.width = SizeChange::SetProportion(width);
+        }
+
+        Ok(())
+    }
+}
+
+/// Window heights in percent, from the top of each column.
+///
+/// `70` gives the top window 70% and lets the others share the rest, `50,30` gives the top two
+/// windows 50% and 30%, and so on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heights(pub Vec<f64>);
+
+impl FromStr for Heights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| {
+                positive_number(value).ok_or_else(|| {
+                    format!("invalid height {:?}, expected a positive number", value)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let total: f64 = values.iter().sum();
+        if total > 100.5 {
+            return Err(format!(
+                "heights must not add up to more than 100%, got {}%",
+                total
+            ));
+        }
+
+        Ok(Heights(values))
+    }
+}
+
+impl<'de> Deserialize<'de> for Heights {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_from_str(deserializer)
+    }
+}
+
+impl Heights {
+    /// Set the window heights of every column with more than one window.
+    pub fn apply(&self, columns: &mut [Column]) -> Result<()> {
+        for column in columns.iter_mut().filter(|c| c.windows.len() > 1) {
+            // The bottom window always gets the remainder
+            let given = &self.0[..self.0.len().min(column.windows.len() - 1)];
+            let remaining_windows = column.windows.len() - given.len();
+            let remaining_height = 100.0 - given.iter().sum::<f64>();
+
+            if remaining_height <= 0.0 {
+                anyhow::bail!(
+                    "Heights {:?} leave no room for the bottom window of a {} window column",
+                    self.0,
+                    column.windows.len()
+                );
+            }
+
+            let shared = remaining_height / remaining_windows as f64;
+            column.heights = given
+                .iter()
+                .copied()
+                .chain(std::iter::repeat(shared))
+                .take(column.windows.len() - 1)
+                .map(SizeChange::SetProportion)
+                .collect();
+        }
+
+        Ok(())
+    }
+}
+
+/// Width to height ratio, parsed from `16:9` or `1.78`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AspectRatio(pub f64);
+
+impl FromStr for AspectRatio {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| {
+            positive_number(value)
+                .ok_or_else(|| format!("invalid aspect ratio {:?}, expected W:H or a number", s))
+        };
+
+        match s.split_once(':') {
+            Some((width, height)) => Ok(AspectRatio(parse(width)? / parse(height)?)),
+            None => Ok(AspectRatio(parse(s)?)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AspectRatio {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_from_str(deserializer)
+    }
+}
+
+/// More windows than any workspace holds, so a typo in a shape can't make it allocate forever.
+const MAX_SHAPE_WINDOWS: usize = 500;
+
+/// Explicit column shape, like `3,2,2` for three columns holding 3, 2 and 2 windows.
+///
+/// `2x3` stands for two columns of three windows and `@50` after a column gives it 50% of the
+/// width. Columns can also be separated with `|`, so `1|2x3@25` is a single window next to two
+/// stacks of three. Columns without a width share what the others leave.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shape {
+    spec: String,
+    /// Window count and width in percent of every column, left to right.
+    pub columns: Vec<(usize, Option<f64>)>,
+}
+
+impl FromStr for Shape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut columns = Vec::new();
+        let mut window_count: usize = 0;
+
+        for part in s.split([',', '|']) {
+            let invalid = |reason: &str| format!("invalid column {:?} in shape: {}", part, reason);
+
+            let (count, width) = match part.split_once('@') {
+                Some((count, width)) => match positive_number(width) {
+                    Some(width) if width <= 100.0 => (count, Some(width)),
+                    _ => return Err(invalid("the width after @ has to be between 0 and 100")),
+                },
+                None => (part, None),
+            };
+            let (repeat, count) = count.split_once('x').unwrap_or(("1", count));
+
+            let parse = |value: &str| match value.trim().parse::<usize>() {
+                Ok(value) if value > 0 => Ok(value),
+                _ => Err(invalid("expected a window count like 3, 2x3 or 3@50")),
+            };
+            let (repeat, count) = (parse(repeat)?, parse(count)?);
+            window_count = count
+                .checked_mul(repeat)
+                .and_then(|windows| window_count.checked_add(windows))
+                .filter(|&total| total <= MAX_SHAPE_WINDOWS)
+                .ok_or_else(|| {
+                    invalid(&format!(
+                        "a shape can't hold more than {} windows",
+                        MAX_SHAPE_WINDOWS
+                    ))
+                })?;
+
+            columns.extend(std::iter::repeat_n((count, width), repeat));
+        }
+
+        let given: f64 = columns.iter().filter_map(|(_, width)| *width).sum();
+        let all_given = columns.iter().all(|(_, width)| width.is_some());
+        // Allow for rounding, e.g. 1@33.3,1@33.3,1@33.3
+        if given > 100.5 || (all_given && (given - 100.0).abs() > 0.5) {
+            return Err(format!(
+                "column widths in shape must add up to 100%, got {}%",
+                given
+            ));
+        }
+        if !all_given && given > 99.5 {
+            return Err(format!(
+                "columns without a width have no room left, the others take {}%",
+                given
+            ));
+        }
+
+        Ok(Shape {
+            spec: s.to_string(),
+            columns,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Shape {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_from_str(deserializer)
+    }
+}
+
+impl Shape {
+    /// Split `window_ids` into the columns of the shape, which has to hold exactly that many.
+    pub fn apply(&self, window_ids: &[u64], fill: FillOrder) -> Result<Vec<Column>> {
+        let capacity: usize = self.columns.iter().map(|(count, _)| count).sum();
+        if capacity != window_ids.len() {
+            anyhow::bail!(
+                "Shape {:?} holds {} windows, but there are {} to arrange{}",
+                self.spec,
+                capacity,
+                window_ids.len(),
+                if capacity > window_ids.len() {
+                    ", remove some from the shape"
+                } else {
+                    ", add some to the shape or leave windows out with the filters"
+                }
+            );
+        }
+
+        let mut columns: Vec<Vec<u64>> = self
+            .columns
+            .iter()
+            .map(|&(count, _)| Vec::with_capacity(count))
+            .collect();
+        match fill {
+            FillOrder::Column => {
+                let mut ids = window_ids.iter().copied();
+                for (column, &(count, _)) in columns.iter_mut().zip(&self.columns) {
+                    column.extend(ids.by_ref().take(count));
+                }
+            }
+            // Reading order, skipping the columns that are full already
+            FillOrder::Row => {
+                let mut ids = window_ids.iter().copied();
+                let rows = self.columns.iter().map(|(count, _)| *count).max();
+                for row in 0..rows.unwrap_or(0) {
+                    for (column, &(count, _)) in columns.iter_mut().zip(&self.columns) {
+                        if row < count {
+                            column.extend(ids.next());
+                        }
+                    }
+                }
+            }
+        }
+
+        let given: f64 = self.columns.iter().filter_map(|(_, width)| *width).sum();
+        let missing = self.columns.iter().filter(|(_, w)| w.is_none()).count();
+        let share = (100.0 - given) / missing.max(1) as f64;
+
+        Ok(columns
+            .into_iter()
+            .zip(&self.columns)
+            .map(|(windows, &(_, width))| {
+                Column::new(windows, SizeChange::SetProportion(width.unwrap_or(share)))
+            })
+            .collect())
+    }
+}
+
+/// Parse a number greater than zero, like the widths, heights and ratios in options.
+fn positive_number(s: &str) -> Option<f64> {
+    s.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| *value > 0.0 && value.is_finite())
+}
+
+/// Deserialize an option from the same string it is written as on the command line.
+fn deserialize_from_str<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+/// Number of grid columns whose cells on a `width` x `height` output come closest to the
+/// `target` aspect ratio.
+///
+/// Ratios are compared on a log scale, so 2:1 and 1:2 are equally far from a square. Ties go to
+/// fewer columns.
+pub fn fit_columns(window_count: usize, width: f64, height: f64, target: AspectRatio) -> usize {
+    let distance = |columns: usize| {
+        let rows = window_count.div_ceil(columns);
+        let cell_aspect = (width / columns as f64) / (height / rows as f64);
+        (cell_aspect / target.0).ln().abs()
+    };
+
+    (1..=window_count.max(1))
+        .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+        .unwrap_or(1)
+}
+
+/// Width of the master column in the master-stack layout, in percent.
+pub const DEFAULT_MASTER_WIDTH: f64 = 60.0;
+
+/// Split `window_ids` into `num_columns` equally wide columns.
+pub fn grid(window_ids: &[u64], num_columns: usize, fill: FillOrder) -> Vec<Column> {
+    let window_count = window_ids.len();
+    let column_width = 100.0 / num_columns as f64;
+
+    (0..num_columns)
+        .map(|column_idx| {
+            let windows = match fill {
+                // Contiguous runs: the first column gets the first few windows
+                FillOrder::Column => {
+                    let start_window = column_start(column_idx, window_count, num_columns);
+                    let end_window = column_start(column_idx + 1, window_count, num_columns);
+                    window_ids[start_window..end_window].to_vec()
+                }
+                // Reading order: window i goes to column i % num_columns
+                FillOrder::Row => window_ids
+                    .iter()
+                    .skip(column_idx)
+                    .step_by(num_columns)
+                    .copied()
+                    .collect(),
+            };
+
+            Column::new(windows, SizeChange::SetProportion(column_width))
+        })
+        .collect()
+}
+
+/// Give `master` its own column of `master_width` percent and stack the other windows into
+/// columns sharing the rest of the width.
+///
+/// `columns` counts the master column too. Without `columns` or `max_rows` everything else goes
+/// into a single stack column.
+pub fn master_stack(
+    window_ids: &[u64],
+    master: u64,
+    master_width: f64,
+    columns: Option<usize>,
+    max_rows: Option<usize>,
+    fill: FillOrder,
+) -> Result<Vec<Column>> {
+    if !(master_width > 0.0 && master_width < 100.0) {
+        anyhow::bail!(
+            "--master-width must be between 0 and 100, got {}",
+            master_width
+        );
+    }
+
+    let stack: Vec<u64> = window_ids
+        .iter()
+        .copied()
+        .filter(|&id| id != master)
+        .collect();
+    if stack.is_empty() {
+        return Ok(vec![Column::new(
+            vec![master],
+            SizeChange::SetProportion(100.0),
+        )]);
+    }
+
+    let stack_columns = match (columns, max_rows) {
+        (Some(columns), _) if columns < 2 => {
+            anyhow::bail!("The master-stack layout needs at least 2 columns")
+        }
+        (None, None) => 1,
+        (columns, max_rows) => grid_columns(stack.len(), columns.map(|c| c - 1), max_rows)?,
+    };
+    let stack_width = (100.0 - master_width) / stack_columns as f64;
+
+    let mut result = vec![Column::new(
+        vec![master],
+        SizeChange::SetProportion(master_width),
+    )];
+    result.extend(
+        grid(&stack, stack_columns, fill)
+            .into_iter()
+            .map(|column| Column {
+                width: SizeChange::SetProportion(stack_width),
+                ..column
+            }),
+    );
+
+    Ok(result)
+}
+
+/// Number of grid columns, honouring the `--columns` and `--max-rows` overrides.
+pub fn grid_columns(
+    window_count: usize,
+    columns: Option<usize>,
+    max_rows: Option<usize>,
+) -> Result<usize> {
+    if columns == Some(0) {
+        anyhow::bail!("--columns must be at least 1");
+    }
+    if max_rows == Some(0) {
+        anyhow::bail!("--max-rows must be at least 1");
+    }
+
+    let num_columns = match (columns, max_rows) {
+        (Some(columns), Some(max_rows)) => {
+            if columns
+                .checked_mul(max_rows)
+                .is_some_and(|capacity| capacity < window_count)
+            {
+                anyhow::bail!(
+                    "{} windows do not fit into {} columns of at most {} rows",
+                    window_count,
+                    columns,
+                    max_rows
+                );
+            }
+            columns
+        }
+        (Some(columns), None) => columns,
+        (None, Some(max_rows)) => window_count.div_ceil(max_rows),
+        (None, None) => num_columns(window_count),
+    };
+
+    if num_columns > window_count {
+        anyhow::bail!(
+            "Cannot fill {} columns with only {} windows",
+            num_columns,
+            window_count
+        );
+    }
+
+    Ok(num_columns)
+}
+
+/// Index of the first window in `column_idx`.
+///
+/// Columns are filled up to `ceil(window_count / num_columns)` windows from the left, so 7
+/// windows in 3 columns go 3, 3, 1. When that would leave columns empty, like 5 windows in 4
+/// columns, the remainder is spread over the leftmost columns instead.
+fn column_start(column_idx: usize, window_count: usize, num_columns: usize) -> usize {
+    let per_column = window_count.div_ceil(num_columns);
+    if per_column * (num_columns - 1) < window_count {
+        return (column_idx * per_column).min(window_count);
+    }
+
+    let base = window_count / num_columns;
+    let extra = window_count % num_columns;
+
+    column_idx * base + column_idx.min(extra)
+}
+
+fn num_columns(window_count: usize) -> usize {
+    if window_count == 0 {
+        return 1;
+    }
+
+    let columns = (window_count as f64).sqrt().ceil() as usize;
+
+    columns.min(window_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape_error(spec: &str) -> String {
+        spec.parse::<Shape>().unwrap_err()
+    }
+
+    #[test]
+    fn shape_columns() {
+        let shape: Shape = "1|2x3@25".parse().unwrap();
+        assert_eq!(
+            shape.columns,
+            vec![(1, None), (3, Some(25.0)), (3, Some(25.0))]
+        );
+
+        let shape: Shape = "4@100".parse().unwrap();
+        assert_eq!(shape.columns, vec![(4, Some(100.0))]);
+    }
+
+    #[test]
+    fn shape_errors() {
+        for spec in ["2x", "0", "x3", "2,,2", "@100"] {
+            assert!(
+                shape_error(spec).contains("expected a window count like 3, 2x3 or 3@50"),
+                "{}",
+                spec
+            );
+        }
+        assert!(shape_error("1@0").contains("the width after @ has to be between 0 and 100"));
+        assert!(shape_error("1@101").contains("the width after @ has to be between 0 and 100"));
+        assert!(shape_error("1@60,1@50").contains("must add up to 100%, got 110%"));
+        assert!(shape_error("1@100,1").contains("columns without a width have no room left"));
+    }
+
+    #[test]
+    fn shape_window_count_is_bounded() {
+        for spec in [
+            "4611686018427387904x1",
+            "18446744073709551615,1",
+            "2x9223372036854775808",
+            "250,251",
+        ] {
+            assert!(
+                shape_error(spec).contains("can't hold more than 500 windows"),
+                "{}",
+                spec
+            );
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Arrange the windows of a niri workspace into compact columns.
+//!
+//! This is the library behind the `niri-compact` binary. Each step of an arrange can be used on
+//! its own:
+//!
+//! - [`NiriClient`] talks to niri over its IPC socket.
+//! - [`select`] decides which windows take part and in which order.
+//! - [`layout`] groups window ids into columns, and [`plan`] turns those into niri actions.
+//!   Custom [`layout::Layout`]s can be added to a [`layout::Registry`] to choose them by name.
+//! - [`run_plan`] sends the actions to niri.
+//!
+//! [`arrange`] does all of it the way the binary does, with the same options. Presets from the
+//! config file aren't part of [`cli::ArrangeArgs`]; apply one with [`config::Preset::apply`]
+//! first if you want them:
+//!
+//! ```no_run
+//! use niri_compact::cli::ArrangeArgs;
+//! use niri_compact::layout::Registry;
+//! use niri_compact::NiriClient;
+//!
+//! let mut client = NiriClient::new(&std::env::var("NIRI_SOCKET")?)?;
+//! niri_compact::arrange(
+//!     &mut client,
+//!     &Registry::default(),
+//!     &ArrangeArgs {
+//!         columns: Some(2),
+//!         ..Default::default()
+//!     },
+//! )?;
+//! # anyhow::Ok(())
+//! ```
+
+#[macro_use]
+pub mod log;
+mod arrange;
+pub mod cli;
+pub mod client;
+pub mod config;
+pub mod daemon;
+pub mod layout;
+pub mod plan;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod select;
+pub mod undo;
+
+pub use arrange::{
+    arrange, find_workspace, plan_workspace, print_plan, run_plan, undo, WorkspacePlan,
+};
+pub use client::NiriClient;
@@ -0,0 +1,53 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+pub const SILENT: u8 = 0;
+pub const QUIET: u8 = 1;
+pub const NORMAL: u8 = 2;
+pub const VERBOSE: u8 = 3;
+
+/// Silent until the binary picks a level, so the library doesn't write to its users' terminal.
+static LEVEL: AtomicU8 = AtomicU8::new(SILENT);
+
+pub fn set_level(level: u8) {
+    LEVEL.store(level, Ordering::Relaxed);
+}
+
+pub fn enabled(level: u8) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level
+}
+
+/// Errors that don't stop the run, like the daemon failing to arrange a workspace.
+macro_rules! error {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::QUIET) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Things the user should know about, like a skipped workspace, hidden by `--quiet`.
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::NORMAL) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Progress messages, hidden by `--quiet`.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::NORMAL) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Step-by-step details, only shown with `--verbose`.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::VERBOSE) {
+            println!($($arg)*);
+        }
+    };
+}
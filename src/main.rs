@@ -1,147 +1,92 @@
 use anyhow::Result;
-use niri_ipc::{Action, ColumnDisplay, Request, Response, SizeChange};
-use std::io::{BufRead, BufReader, Write};
-use std::os::unix::net::UnixStream;
-
-struct NiriClient {
-    reader: BufReader<UnixStream>,
-    writer: UnixStream,
-}
-
-impl NiriClient {
-    fn new(socket_path: &str) -> Result<Self> {
-        let stream = UnixStream::connect(socket_path)?;
-        let reader = BufReader::new(stream.try_clone()?);
-        let writer = stream;
-
-        Ok(NiriClient { reader, writer })
-    }
-
-    fn action(&mut self, action: Action) -> Result<niri_ipc::Reply> {
-        self.execute(Request::Action(action))
-    }
-    fn execute(&mut self, request: Request) -> Result<niri_ipc::Reply> {
-        writeln!(self.writer, "{}", serde_json::to_string(&request)?)?;
-
-        let mut response_line = String::new();
-        self.reader.read_line(&mut response_line)?;
-
-        Ok(serde_json::from_str(&response_line)?)
-    }
-
-    fn get_windows(&mut self) -> Result<Vec<niri_ipc::Window>> {
-        match self.execute(Request::Windows)? {
-            Ok(Response::Windows(windows)) => Ok(windows),
-            _ => Err(anyhow::anyhow!("Failed to get windows")),
-        }
-    }
-
-    fn get_workspaces(&mut self) -> Result<Vec<niri_ipc::Workspace>> {
-        match self.execute(Request::Workspaces)? {
-            Ok(Response::Workspaces(workspaces)) => Ok(workspaces),
-            _ => Err(anyhow::anyhow!("Failed to get workspaces")),
-        }
-    }
-}
+use niri_compact::cli::{ArrangeArgs, Cli, Command, DaemonArgs, PresetArgs};
+use niri_compact::client::NiriClient;
+use niri_compact::layout::Registry;
+use niri_compact::{arrange, config, daemon, log, undo};
+use std::time::Duration;
 
 fn main() -> Result<()> {
-    let socket_path = std::env::var("NIRI_SOCKET")
-        .map_err(|_| anyhow::anyhow!("NIRI_SOCKET environment variable not set"))?;
-
-    let mut client = NiriClient::new(&socket_path)?;
-
-    let windows = client.get_windows()?;
-    let workspaces = client.get_workspaces()?;
-
-    let current_workspace = workspaces
-        .iter()
-        .find(|ws| ws.is_focused)
-        .ok_or_else(|| anyhow::anyhow!("No focused workspace found"))?;
-
-    // Get current workspace windows
-    let current_workspace_windows: Vec<_> = windows
-        .iter()
-        .filter(|w| w.workspace_id == Some(current_workspace.id))
-        .collect();
-
-    if current_workspace_windows.is_empty() {
-        println!("No windows found on current workspace");
-        return Ok(());
-    }
-
-    let window_count = current_workspace_windows.len();
-    println!("✅ Found {} windows on current workspace", window_count);
-
-    if window_count == 0 {
-        println!("❌ No windows to arrange");
-        return Ok(());
-    }
-
-    let num_columns = num_columns(window_count);
-    let windows_per_column = window_count.div_ceil(num_columns);
-    let column_width = 100.0 / num_columns as f64;
-
-    println!(
-        "📐 Creating {} columns with up to {} windows per column",
-        num_columns, windows_per_column
-    );
-
-    for window in current_workspace_windows.iter() {
-        let _ = client.action(Action::FocusWindow { id: window.id })?;
-        let _ = client.action(Action::ExpelWindowFromColumn {})?;
-    }
-
-    // Process each column from left to right
-    for column_idx in 0..num_columns {
-        let start_window = column_idx * windows_per_column;
-        let end_window = ((column_idx + 1) * windows_per_column).min(window_count);
-
-        if start_window >= window_count {
-            println!("   ✅ Column {}: No more windows to process", column_idx);
-            break; // No more windows to process
+    let cli = Cli::parse_args();
+
+    log::set_level(match (cli.quiet, cli.verbose) {
+        (true, _) => log::QUIET,
+        (false, 0) => log::NORMAL,
+        (false, _) => log::VERBOSE,
+    });
+
+    let config = config::Config::load(cli.config.as_deref())?;
+    let resolve = |args: PresetArgs| -> Result<ArrangeArgs> {
+        Ok(config.preset(args.preset.as_deref())?.apply(args.arrange))
+    };
+
+    #[cfg(feature = "scripting")]
+    let registry = {
+        let mut registry = Registry::default();
+        if let Some(dir) = config::layouts_dir(cli.config.as_deref()) {
+            niri_compact::script::register_dir(&mut registry, &dir)?;
         }
-
-        println!(
-            "🏛️  Building column {} with windows {}-{}",
-            column_idx,
-            start_window,
-            end_window - 1
-        );
-
-        let _ = client.action(Action::FocusColumn {
-            index: column_idx + 1,
-        })?;
-        let _ = client.action(Action::SetColumnDisplay {
-            display: ColumnDisplay::Normal,
-        })?;
-        let _ = client.action(Action::SetWindowWidth {
-            id: None,
-            change: SizeChange::SetProportion(column_width),
-        })?;
-
-        // Consume additional windows into this column
-        let windows_to_consume = end_window - start_window - 1;
-        for _i in 0..windows_to_consume {
-            let _ = client.action(Action::ConsumeWindowIntoColumn {})?;
-        }
-    }
-
-    let _ = client.action(Action::FocusColumnFirst {})?;
-
-    println!(
-        "✅ Successfully arranged {} windows into {} columns!",
-        window_count, num_columns
-    );
-
-    Ok(())
-}
-
-fn num_columns(window_count: usize) -> usize {
-    if window_count == 0 {
-        return 1;
+        registry
+    };
+    #[cfg(not(feature = "scripting"))]
+    let registry = Registry::default();
+
+    let socket_path = || {
+        std::env::var("NIRI_SOCKET")
+            .map_err(|_| anyhow::anyhow!("NIRI_SOCKET environment variable not set"))
+    };
+
+    let mut client = match &cli.replay {
+        Some(path) => NiriClient::replay(path)?,
+        None => NiriClient::new(&socket_path()?)?,
+    };
+    if let Some(path) = &cli.record {
+        client.record(path)?;
     }
 
-    let columns = (window_count as f64).sqrt().ceil() as usize;
+    let result = match cli.command {
+        Some(Command::Arrange(args)) => arrange(&mut client, &registry, &resolve(args)?),
+        Some(Command::Plan(args)) => arrange(
+            &mut client,
+            &registry,
+            &ArrangeArgs {
+                dry_run: true,
+                ..resolve(args)?
+            },
+        ),
+        Some(Command::Undo(args)) => undo(&mut client, &args),
+        Some(Command::Daemon(DaemonArgs {
+            watch,
+            debounce,
+            arrange,
+        })) => {
+            if cli.replay.is_some() {
+                anyhow::bail!("The daemon needs a running niri, it can't replay a transcript");
+            }
+            let arrange = resolve(arrange)?;
+            // The daemon picks the workspaces from --watch and always applies its plans
+            for (used, flag) in [
+                (arrange.workspace.is_some(), "--workspace"),
+                (arrange.output.is_some(), "--output"),
+                (arrange.all_workspaces, "--all-workspaces"),
+                (arrange.dry_run, "--dry-run"),
+                (arrange.json, "--json"),
+            ] {
+                if used {
+                    anyhow::bail!("{} can't be used with the daemon", flag);
+                }
+            }
+            daemon::run(
+                &mut client,
+                &socket_path()?,
+                &registry,
+                &watch,
+                Duration::from_millis(debounce),
+                &arrange,
+            )
+        }
+        None => arrange(&mut client, &registry, &resolve(cli.arrange)?),
+    };
 
-    columns.min(window_count)
+    result?;
+    client.finish_replay()
 }
@@ -0,0 +1,167 @@
+use crate::layout::Column;
+use niri_ipc::{Action, ColumnDisplay, SizeChange, Window};
+use std::collections::BTreeMap;
+
+/// Tiled windows of a workspace grouped into its columns, left to right and top to bottom.
+pub fn current_columns(windows: &[Window], workspace_id: u64) -> Vec<Vec<&Window>> {
+    let mut columns: BTreeMap<usize, Vec<(usize, &Window)>> = BTreeMap::new();
+
+    for window in windows
+        .iter()
+        .filter(|w| w.workspace_id == Some(workspace_id))
+    {
+        if let Some((column, tile)) = window.layout.pos_in_scrolling_layout {
+            columns.entry(column).or_default().push((tile, window));
+        }
+    }
+
+    columns
+        .into_values()
+        .map(|mut tiles| {
+            tiles.sort_by_key(|(tile, _)| *tile);
+            tiles.into_iter().map(|(_, w)| w).collect()
+        })
+        .collect()
+}
+
+/// Turn the target `columns` into the actions that build them out of the `current` ones.
+///
+/// Columns are built left to right. A column that already holds the right windows in the right
+/// place is left alone apart from its size and display. Otherwise its windows are expelled into
+/// columns of their own where needed, moved into place one after the other and consumed into
+/// the first one. Windows that aren't part of `columns` end up to the right of the arranged ones.
+///
+/// Running the plan twice only resizes columns the second time, which niri doesn't animate when
+/// nothing changes. The plan may leave focus anywhere; callers decide where it should end up.
+pub fn plan(columns: &[Column], current: &[Vec<u64>]) -> Vec<Action> {
+    let mut actions = Vec::new();
+    // What the workspace looks like after the actions so far. Windows missing from `current`,
+    // like floating ones being tiled, are only known once they've been moved into place.
+    let mut layout = current.to_vec();
+
+    for (column_idx, column) in columns.iter().enumerate() {
+        if layout.get(column_idx) == Some(&column.windows) {
+            // niri doesn't tell which columns are tabbed, so only switch the ones that should be
+            if column.display != ColumnDisplay::Normal {
+                actions.push(Action::FocusColumn {
+                    index: column_idx + 1,
+                });
+                actions.push(Action::SetColumnDisplay {
+                    display: column.display,
+                });
+            }
+            actions.push(Action::SetWindowWidth {
+                id: Some(column.windows[0]),
+                change: column.width,
+            });
+            push_heights(&mut actions, column);
+            continue;
+        }
+
+        // Give every window of the column a column of its own, in order from `column_idx`
+        for (position, &id) in column.windows.iter().enumerate() {
+            let target = column_idx + position;
+            let found = layout
+                .iter()
+                .position(|windows| windows.contains(&id))
+                .map(|idx| (idx, layout[idx].len()));
+            if found == Some((target, 1)) {
+                continue;
+            }
+
+            actions.push(Action::FocusWindow { id });
+            if found.is_none_or(|(_, len)| len > 1) {
+                actions.push(Action::ExpelWindowFromColumn {});
+            }
+            actions.push(Action::MoveColumnToIndex { index: target + 1 });
+
+            if let Some((idx, _)) = found {
+                layout[idx].retain(|&window| window != id);
+                if layout[idx].is_empty() {
+                    layout.remove(idx);
+                }
+            }
+            layout.insert(target.min(layout.len()), vec![id]);
+        }
+
+        actions.push(Action::FocusColumn {
+            index: column_idx + 1,
+        });
+        actions.push(Action::SetColumnDisplay {
+            display: column.display,
+        });
+        actions.push(Action::SetWindowWidth {
+            id: None,
+            change: column.width,
+        });
+
+        // Consume additional windows into this column
+        for _ in 1..column.windows.len() {
+            actions.push(Action::ConsumeWindowIntoColumn {});
+        }
+        let end = (column_idx + column.windows.len()).min(layout.len());
+        layout.splice(column_idx..end, [column.windows.clone()]);
+
+        push_heights(&mut actions, column);
+    }
+
+    actions
+}
+
+fn push_heights(actions: &mut Vec<Action>, column: &Column) {
+    for (&id, &change) in column
+        .windows
+        .iter()
+        .zip(&column.heights)
+        .take(column.windows.len().saturating_sub(1))
+    {
+        actions.push(Action::SetWindowHeight {
+            id: Some(id),
+            change,
+        });
+    }
+}
+
+/// Short human-readable description of an action.
+pub fn describe(action: &Action) -> String {
+    match action {
+        Action::FocusWorkspace { reference } => format!("focus workspace {:?}", reference),
+        Action::FocusWindow { id } => format!("focus window {}", id),
+        Action::ExpelWindowFromColumn {} => "expel window from column".to_string(),
+        Action::FocusColumn { index } => format!("focus column {}", index),
+        Action::SetColumnDisplay { display } => format!("set column display to {:?}", display),
+        Action::SetWindowWidth {
+            id: Some(id),
+            change: SizeChange::SetProportion(proportion),
+        } => format!(
+            "set width of column with window {} to {:.1}%",
+            id, proportion
+        ),
+        Action::SetWindowWidth {
+            id: Some(id),
+            change: SizeChange::SetFixed(pixels),
+        } => format!("set width of column with window {} to {}px", id, pixels),
+        Action::SetWindowWidth {
+            change: SizeChange::SetProportion(proportion),
+            ..
+        } => format!("set column width to {:.1}%", proportion),
+        Action::SetWindowWidth {
+            change: SizeChange::SetFixed(pixels),
+            ..
+        } => format!("set column width to {}px", pixels),
+        Action::SetWindowHeight {
+            id: Some(id),
+            change: SizeChange::SetProportion(proportion),
+        } => format!("set height of window {} to {:.1}%", id, proportion),
+        Action::SetWindowHeight {
+            id: Some(id),
+            change: SizeChange::SetFixed(pixels),
+        } => format!("set height of window {} to {}px", id, pixels),
+        Action::MoveColumnToIndex { index } => format!("move column to index {}", index),
+        Action::ConsumeWindowIntoColumn {} => "consume window into column".to_string(),
+        Action::FocusColumnFirst {} => "focus first column".to_string(),
+        Action::MoveWindowToTiling { id: Some(id) } => format!("tile floating window {}", id),
+        Action::MoveWindowToFloating { id: Some(id) } => format!("float window {}", id),
+        other => format!("{:?}", other),
+    }
+}
@@ -0,0 +1,241 @@
+//! Layouts written in [Rhai](https://rhai.rs).
+//!
+//! A layout script defines `fn layout(windows, output)`. `windows` is an array of maps with the
+//! `id`, `app_id`, `title`, `pid` and `focused` of every selected window, in arrange order, and
+//! `output` is a map with the `width` and `height` of the output, or `()` if niri doesn't know
+//! them. It returns the columns from left to right, each either an array of window ids or a map:
+//!
+//! ```rhai
+//! fn layout(windows, output) {
+//!     let ids = windows.map(|w| w.id);
+//!     [#{ windows: ids.extract(0, 1), width: 70 }, #{ windows: ids.extract(1), tabbed: true }]
+//! }
+//! ```
+//!
+//! `width` is in percent of the output and defaults to an equal share of what the other columns
+//! leave, so the widths given can't add up to more than 100. `heights` are percentages for the
+//! windows from the top, like `--heights`.
+
+use crate::cli::ArrangeArgs;
+use crate::layout::{Column, Layout, LayoutInput, Registry};
+use anyhow::{Context, Result};
+use niri_ipc::{ColumnDisplay, SizeChange};
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use std::path::Path;
+
+/// Keeps a runaway script from hanging the arrange.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+pub struct ScriptLayout {
+    engine: Engine,
+    ast: AST,
+}
+
+impl ScriptLayout {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|err| anyhow::anyhow!("{}", err))
+            .with_context(|| format!("Failed to compile {}", path.display()))?;
+
+        Ok(ScriptLayout { engine, ast })
+    }
+}
+
+impl Layout for ScriptLayout {
+    fn columns(&self, input: &LayoutInput) -> Result<Vec<Column>> {
+        let windows: Array = input
+            .windows
+            .iter()
+            .map(|w| {
+                let mut window = Map::new();
+                window.insert("id".into(), (w.id as i64).into());
+                window.insert("app_id".into(), optional(w.app_id.clone()));
+                window.insert("title".into(), optional(w.title.clone()));
+                window.insert("pid".into(), optional(w.pid.map(i64::from)));
+                window.insert("focused".into(), (input.active_window == Some(w.id)).into());
+                window.into()
+            })
+            .collect();
+
+        let output = match input.output_size {
+            Some((width, height)) => {
+                let mut output = Map::new();
+                output.insert("width".into(), width.into());
+                output.insert("height".into(), height.into());
+                output.into()
+            }
+            None => Dynamic::UNIT,
+        };
+
+        let result: Array = self
+            .engine
+            .call_fn(&mut Scope::new(), &self.ast, "layout", (windows, output))
+            .map_err(|err| anyhow::anyhow!("{}", err))?;
+
+        columns(result)
+    }
+
+    fn needs_output_size(&self, _args: &ArrangeArgs) -> bool {
+        // There's no telling whether the script looks at it
+        true
+    }
+}
+
+/// Register every `*.rhai` file in `dir` as a layout named after the file.
+///
+/// A missing directory just means there are no scripts. Scripts that don't compile are reported
+/// and skipped, so they only break the runs that ask for them.
+pub fn register_dir(registry: &mut Registry, dir: &Path) -> Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", dir.display()));
+        }
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "rhai") {
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                match ScriptLayout::load(&path) {
+                    Ok(layout) => registry.register(name, layout),
+                    Err(err) => warn!("⚠️  Skipping layout {:?}: {:#}", name, err),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn optional<T: Into<Dynamic>>(value: Option<T>) -> Dynamic {
+    value.map_or(Dynamic::UNIT, Into::into)
+}
+
+/// Turn what `layout` returned into columns.
+fn columns(result: Array) -> Result<Vec<Column>> {
+    let mut columns = Vec::new();
+    let mut widths = Vec::new();
+
+    for (column_idx, value) in result.into_iter().enumerate() {
+        let context = || format!("Column {} returned by the layout script", column_idx + 1);
+
+        let (windows, width, heights, tabbed) = if value.is_array() {
+            (value, None, Vec::new(), false)
+        } else if let Some(mut map) = value.try_cast::<Map>() {
+            let windows = map.remove("windows").unwrap_or(Dynamic::UNIT);
+            let width = map
+                .remove("width")
+                .map(|width| percent(&width))
+                .transpose()
+                .with_context(context)?;
+            let heights: Vec<f64> = match map.remove("heights") {
+                Some(heights) => array(heights)
+                    .with_context(context)?
+                    .iter()
+                    .map(percent)
+                    .collect::<Result<_>>()
+                    .with_context(context)?,
+                None => Vec::new(),
+            };
+            let total: f64 = heights.iter().sum();
+            if total > 100.5 {
+                return Err(anyhow::anyhow!(
+                    "heights must not add up to more than 100%, got {}%",
+                    total
+                ))
+                .with_context(context);
+            }
+            let tabbed = match map.remove("tabbed") {
+                Some(tabbed) => tabbed
+                    .as_bool()
+                    .map_err(|_| anyhow::anyhow!("tabbed has to be true or false"))
+                    .with_context(context)?,
+                None => false,
+            };
+            if let Some(key) = map.keys().next() {
+                return Err(anyhow::anyhow!("Unknown key {:?}", key.as_str()))
+                    .with_context(context);
+            }
+            (windows, width, heights, tabbed)
+        } else {
+            anyhow::bail!("{} is neither an array nor a map", context());
+        };
+
+        let windows = array(windows)
+            .with_context(context)?
+            .iter()
+            .map(|id| {
+                id.as_int()
+                    .ok()
+                    .and_then(|id| u64::try_from(id).ok())
+                    .ok_or_else(|| anyhow::anyhow!("{} is not a window id", id))
+            })
+            .collect::<Result<_>>()
+            .with_context(context)?;
+
+        let mut column = Column::new(windows, SizeChange::SetProportion(0.0));
+        column.heights = heights.into_iter().map(SizeChange::SetProportion).collect();
+        if tabbed {
+            column.display = ColumnDisplay::Tabbed;
+        }
+        columns.push(column);
+        widths.push(width);
+    }
+
+    // Columns without a width share what the others leave
+    let given: f64 = widths.iter().flatten().sum();
+    if given > 100.5 {
+        anyhow::bail!(
+            "Column widths returned by the layout script add up to {}%, more than 100%",
+            given
+        );
+    }
+    let missing: Vec<usize> = widths
+        .iter()
+        .enumerate()
+        .filter(|(_, width)| width.is_none())
+        .map(|(column_idx, _)| column_idx + 1)
+        .collect();
+    let share = (100.0 - given) / missing.len().max(1) as f64;
+    if !missing.is_empty() && share <= 0.0 {
+        anyhow::bail!(
+            "Columns {:?} returned by the layout script have no width and the others leave no \
+             room for them",
+            missing
+        );
+    }
+    for (column, width) in columns.iter_mut().zip(widths) {
+        column.width = SizeChange::SetProportion(width.unwrap_or(share));
+    }
+
+    Ok(columns)
+}
+
+fn array(value: Dynamic) -> Result<Array> {
+    let type_name = value.type_name();
+    value
+        .try_cast::<Array>()
+        .ok_or_else(|| anyhow::anyhow!("Expected an array, got {}", type_name))
+}
+
+/// A width or height, in percent of the output.
+fn percent(value: &Dynamic) -> Result<f64> {
+    let percent = number(value)?;
+    if !(percent > 0.0 && percent <= 100.0) {
+        anyhow::bail!("{} is not a percentage between 0 and 100", percent);
+    }
+    Ok(percent)
+}
+
+fn number(value: &Dynamic) -> Result<f64> {
+    value
+        .as_float()
+        .or_else(|_| value.as_int().map(|int| int as f64))
+        .map_err(|_| anyhow::anyhow!("Expected a number, got {}", value.type_name()))
+}
@@ -0,0 +1,50 @@
+use crate::cli::{ArrangeArgs, SortKey};
+use niri_ipc::Window;
+use regex::Regex;
+use std::cmp::Ordering;
+
+/// Whether `window` takes part in the arrangement.
+///
+/// Windows without an app id or title are matched as if it were empty.
+pub fn is_selected(args: &ArrangeArgs, window: &Window) -> bool {
+    if window.is_floating && !args.include_floating {
+        return false;
+    }
+
+    let app_id = window.app_id.as_deref().unwrap_or_default();
+    let title = window.title.as_deref().unwrap_or_default();
+
+    (args.include_app.is_empty() || any_match(&args.include_app, app_id))
+        && (args.include_title.is_empty() || any_match(&args.include_title, title))
+        && !any_match(&args.exclude_app, app_id)
+        && !any_match(&args.exclude_title, title)
+}
+
+fn any_match(patterns: &[Regex], text: &str) -> bool {
+    patterns.iter().any(|pattern| pattern.is_match(text))
+}
+
+/// Sort `windows` by `key`, breaking ties by window id so the order is always the same.
+pub fn sort(windows: &mut [&Window], key: SortKey) {
+    windows.sort_by(|a, b| compare(a, b, key).then(a.id.cmp(&b.id)));
+}
+
+fn compare(a: &Window, b: &Window, key: SortKey) -> Ordering {
+    match key {
+        SortKey::AppId => a.app_id.cmp(&b.app_id),
+        SortKey::Title => a.title.cmp(&b.title),
+        SortKey::Pid => a.pid.cmp(&b.pid),
+        SortKey::Id => Ordering::Equal,
+        // Tiled windows by column and tile, then floating ones by where they are in the view
+        SortKey::Position => {
+            let tiled = |w: &Window| w.layout.pos_in_scrolling_layout.unwrap_or((usize::MAX, 0));
+            let view = |w: &Window| w.layout.tile_pos_in_workspace_view.unwrap_or_default();
+
+            tiled(a).cmp(&tiled(b)).then_with(|| {
+                let (ax, ay) = view(a);
+                let (bx, by) = view(b);
+                ax.total_cmp(&bx).then(ay.total_cmp(&by))
+            })
+        }
+    }
+}
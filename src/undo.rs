@@ -0,0 +1,120 @@
+use crate::layout::Column;
+use crate::plan;
+use anyhow::{Context, Result};
+use niri_ipc::{ColumnDisplay, SizeChange, Window, Workspace};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Column layout of a workspace as it was before arranging it.
+///
+/// One arrange can touch several workspaces, so the state file holds a list of these.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    pub workspace_id: u64,
+    pub columns: Vec<Column>,
+    /// Floating windows, so tiling them in can be undone as well.
+    #[serde(default)]
+    pub floating: Vec<u64>,
+    /// Active window of the workspace, which is the focused one unless it was in the background.
+    pub focused_window: Option<u64>,
+}
+
+impl Snapshot {
+    /// Record the tiled columns of `workspace`, left to right.
+    pub fn capture(windows: &[Window], workspace: &Workspace) -> Self {
+        let workspace_id = workspace.id;
+        let columns = plan::current_columns(windows, workspace_id)
+            .into_iter()
+            .map(|tiles| Column {
+                // niri fixed sizes are in logical pixels and exclude the borders
+                width: SizeChange::SetFixed(tiles[0].layout.window_size.0),
+                heights: tiles[..tiles.len() - 1]
+                    .iter()
+                    .map(|w| SizeChange::SetFixed(w.layout.window_size.1))
+                    .collect(),
+                windows: tiles.into_iter().map(|w| w.id).collect(),
+                // niri doesn't tell, so tabbed columns come back stacked
+                display: ColumnDisplay::Normal,
+            })
+            .collect();
+
+        Snapshot {
+            workspace_id,
+            columns,
+            floating: windows
+                .iter()
+                .filter(|w| w.workspace_id == Some(workspace_id) && w.is_floating)
+                .map(|w| w.id)
+                .collect(),
+            focused_window: workspace.active_window_id,
+        }
+    }
+
+    /// Drop windows that closed or left the workspace since the snapshot was taken.
+    pub fn retain_present(&mut self, windows: &[Window]) {
+        let present = |id: &u64| {
+            windows
+                .iter()
+                .any(|w| w.id == *id && w.workspace_id == Some(self.workspace_id))
+        };
+
+        for column in &mut self.columns {
+            if column.windows.iter().any(|id| !present(id)) {
+                // The heights belong to windows that may be gone now
+                column.heights.clear();
+            }
+            column.windows.retain(present);
+        }
+        self.columns.retain(|column| !column.windows.is_empty());
+        self.floating.retain(present);
+        self.focused_window = self.focused_window.filter(present);
+    }
+}
+
+/// Replace the saved snapshots in `dir` with the ones from the latest arrange.
+pub fn save(dir: Option<&Path>, snapshots: &[Snapshot]) -> Result<()> {
+    let path = state_file(dir)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+
+    std::fs::write(&path, serde_json::to_string(snapshots)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Load the snapshots of the last arrange from `dir`, if there are any.
+pub fn load(dir: Option<&Path>) -> Result<Option<Vec<Snapshot>>> {
+    let path = state_file(dir)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(Some(serde_json::from_str(&contents).with_context(
+        || format!("Failed to parse {}", path.display()),
+    )?))
+}
+
+pub fn remove(dir: Option<&Path>) -> Result<()> {
+    let path = state_file(dir)?;
+    std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))
+}
+
+/// `undo.json` in `dir`, or in `$XDG_RUNTIME_DIR/niri-compact` if there's no `dir`.
+///
+/// There's no fallback: a shared directory like `/tmp` would let other users read or replace
+/// the state.
+fn state_file(dir: Option<&Path>) -> Result<PathBuf> {
+    let dir = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .context("XDG_RUNTIME_DIR is not set, so there's nowhere to keep the undo state")?
+            .join("niri-compact"),
+    };
+
+    Ok(dir.join("undo.json"))
+}
@@ -0,0 +1,428 @@
+mod common;
+
+use common::{FakeNiri, State};
+use niri_ipc::{Request, SizeChange};
+
+#[test]
+fn default_is_a_square_grid() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]]));
+
+    niri.run_ok(&[]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 2], vec![3, 4]]);
+    let state = niri.state();
+    assert_eq!(state.widths[&1], SizeChange::SetProportion(50.0));
+    assert_eq!(state.widths[&3], SizeChange::SetProportion(50.0));
+}
+
+#[test]
+fn default_grid_fills_the_left_columns_first() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1, 2, 3, 4, 5, 6, 7]]));
+
+    niri.run_ok(&[]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 2, 3], vec![4, 5, 6], vec![7]]);
+}
+
+#[test]
+fn no_column_is_left_empty() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1, 2, 3, 4, 5]]));
+
+    niri.run_ok(&["--columns", "4"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 2], vec![3], vec![4], vec![5]]);
+}
+
+#[test]
+fn extra_windows_go_to_the_left_columns() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1, 2, 3, 4, 5]]));
+
+    niri.run_ok(&["arrange", "--columns", "3"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 2], vec![3, 4], vec![5]]);
+}
+
+#[test]
+fn fill_by_row() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]]));
+
+    niri.run_ok(&["--fill", "row"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 3], vec![2, 4]]);
+}
+
+#[test]
+fn master_stack_uses_the_focused_window() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]])
+            .focus(3),
+    );
+
+    niri.run_ok(&["-l", "master-stack"]);
+
+    assert_eq!(niri.columns(1), vec![vec![3], vec![1, 2, 4]]);
+    assert_eq!(niri.state().widths[&3], SizeChange::SetProportion(60.0));
+}
+
+#[test]
+fn filtered_out_windows_end_up_on_the_right() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]])
+            .app(2, "firefox"),
+    );
+
+    niri.run_ok(&["--exclude-app", "firefox"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 3], vec![4], vec![2]]);
+}
+
+#[test]
+fn include_floating_tiles_them_in() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3]])
+            .floating(1, 4),
+    );
+
+    niri.run_ok(&["--include-floating"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 2], vec![3, 4]]);
+    assert!(niri.state().floating_windows(1).is_empty());
+}
+
+#[test]
+fn arranged_workspace_is_left_alone() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]]));
+
+    niri.run_ok(&[]);
+    niri.state().actions.clear();
+    niri.run_ok(&[]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 2], vec![3, 4]]);
+    let state = niri.state();
+    assert_eq!(state.count("ExpelWindowFromColumn"), 0);
+    assert_eq!(state.count("ConsumeWindowIntoColumn"), 0);
+}
+
+#[test]
+fn columns_already_in_place_are_kept() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3, 4]]));
+
+    niri.run_ok(&["--columns", "2"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 2], vec![3, 4]]);
+    let state = niri.state();
+    assert_eq!(state.count("ExpelWindowFromColumn"), 0);
+    assert_eq!(state.count("MoveColumnToIndex"), 0);
+    assert_eq!(state.count("ConsumeWindowIntoColumn"), 1);
+}
+
+#[test]
+fn focus_goes_back_to_the_focused_window() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]])
+            .focus(4),
+    );
+
+    niri.run_ok(&[]);
+    assert_eq!(niri.state().focused_window(), Some(4));
+
+    niri.run_ok(&["--columns", "1", "--focus-first"]);
+    assert_eq!(niri.state().focused_window(), Some(1));
+}
+
+#[test]
+fn background_workspace_keeps_focus_where_it_was() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2]])
+            .workspace(2, "DP-1", &[&[3], &[4], &[5], &[6]])
+            .named(2, "chat")
+            .focus(2),
+    );
+
+    niri.run_ok(&["--workspace", "chat"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1], vec![2]]);
+    assert_eq!(niri.columns(2), vec![vec![3, 4], vec![5, 6]]);
+    let state = niri.state();
+    assert_eq!(state.focused_workspace, 1);
+    assert_eq!(state.focused_window(), Some(2));
+}
+
+#[test]
+fn all_workspaces() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]])
+            .workspace(2, "HDMI-A-1", &[&[5], &[6], &[7], &[8]])
+            .focus(2),
+    );
+
+    niri.run_ok(&["--all-workspaces"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 2], vec![3, 4]]);
+    assert_eq!(niri.columns(2), vec![vec![5, 6], vec![7, 8]]);
+    assert_eq!(niri.state().focused_window(), Some(2));
+}
+
+#[test]
+fn workspaces_that_dont_fit_are_skipped() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]])
+            .workspace(2, "HDMI-A-1", &[&[5]]),
+    );
+
+    let output = niri.run(&["--all-workspaces", "--columns", "2"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Skipping workspace 1: Cannot fill 2 columns with only 1 windows"));
+    assert_eq!(niri.columns(1), vec![vec![1, 2], vec![3, 4]]);
+    assert_eq!(niri.columns(2), vec![vec![5]]);
+
+    let output = niri.run(&["--all-workspaces", "--columns", "5"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn dry_run_sends_no_actions() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]]));
+
+    let stdout = niri.run_ok(&["plan"]);
+
+    assert!(stdout.contains("consume window into column"));
+    assert_eq!(niri.columns(1), vec![vec![1], vec![2], vec![3], vec![4]]);
+    assert!(niri.state().actions.is_empty());
+}
+
+#[test]
+fn failed_action_stops_the_plan() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]])
+            .fail("ConsumeWindowIntoColumn"),
+    );
+
+    let output = niri.run(&[]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("simulated failure of ConsumeWindowIntoColumn"));
+    assert_eq!(niri.state().count("ConsumeWindowIntoColumn"), 1);
+
+    let output = niri.run(&["--keep-going"]);
+
+    assert!(!output.status.success());
+    assert_eq!(niri.state().count("ConsumeWindowIntoColumn"), 3);
+}
+
+#[test]
+fn preset_from_config() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]]));
+    niri.write_config(
+        r#"
+        [presets.wide]
+        columns = 1
+        "#,
+    );
+
+    niri.run_ok(&["--preset", "wide"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 2, 3, 4]]);
+}
+
+#[test]
+fn shape_with_widths() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1, 2, 3, 4, 5, 6, 7]]));
+
+    niri.run_ok(&["--shape", "1@40|2x3"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1], vec![2, 3, 4], vec![5, 6, 7]]);
+    let state = niri.state();
+    assert_eq!(state.widths[&1], SizeChange::SetProportion(40.0));
+    assert_eq!(state.widths[&2], SizeChange::SetProportion(30.0));
+}
+
+#[test]
+fn shape_filled_by_row() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3], &[4], &[5]]));
+
+    niri.run_ok(&["--shape", "1,2,2", "--fill", "row"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1], vec![2, 4], vec![3, 5]]);
+}
+
+#[test]
+fn shape_has_to_match_the_window_count() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3]]));
+
+    let output = niri.run(&["--shape", "2,2"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("holds 4 windows, but there are 3 to arrange"));
+    assert!(niri.state().actions.is_empty());
+}
+
+#[test]
+fn command_line_turns_off_preset_switches() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]])
+            .focus(4),
+    );
+    niri.write_config(
+        r#"
+        [presets.default]
+        focus-first = true
+        "#,
+    );
+
+    niri.run_ok(&["--no-focus-first"]);
+    assert_eq!(niri.state().focused_window(), Some(4));
+
+    niri.run_ok(&["--columns", "1"]);
+    assert_eq!(niri.state().focused_window(), Some(1));
+}
+
+#[test]
+fn widths_as_percentages_or_ratios() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3]]));
+
+    niri.run_ok(&["--columns", "2", "--widths", "60,40"]);
+
+    let state = niri.state();
+    assert_eq!(state.widths[&1], SizeChange::SetProportion(60.0));
+    assert_eq!(state.widths[&3], SizeChange::SetProportion(40.0));
+    drop(state);
+
+    niri.run_ok(&["--columns", "3", "--widths", "2:1:1"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1], vec![2], vec![3]]);
+    let state = niri.state();
+    assert_eq!(state.widths[&1], SizeChange::SetProportion(50.0));
+    assert_eq!(state.widths[&2], SizeChange::SetProportion(25.0));
+    assert_eq!(state.widths[&3], SizeChange::SetProportion(25.0));
+}
+
+#[test]
+fn heights_from_the_top() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3], &[4], &[5]]));
+
+    niri.run_ok(&["--columns", "2", "--heights", "50"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 2, 3], vec![4, 5]]);
+    let state = niri.state();
+    assert_eq!(state.heights[&1], SizeChange::SetProportion(50.0));
+    assert_eq!(state.heights[&2], SizeChange::SetProportion(25.0));
+    assert_eq!(state.heights[&4], SizeChange::SetProportion(50.0));
+    assert!(!state.heights.contains_key(&3));
+}
+
+#[test]
+fn heights_have_to_leave_room_for_the_bottom_window() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3]]));
+
+    let output = niri.run(&["--columns", "1", "--heights", "60,40"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("leave no room for the bottom window of a 3 window column"));
+    assert!(niri.state().actions.is_empty());
+}
+
+#[test]
+fn cell_aspect_follows_the_output_size() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]])
+            .output_size("DP-1", 1080, 1920),
+    );
+
+    // Stacking all four windows of a portrait output gives the widest cells
+    niri.run_ok(&["--cell-aspect", "16:9"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1, 2, 3, 4]]);
+}
+
+#[test]
+fn sort_by_app_id() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]])
+            .app(1, "kitty")
+            .app(2, "firefox")
+            .app(3, "zathura")
+            .app(4, "alacritty"),
+    );
+
+    niri.run_ok(&["--sort", "app-id"]);
+
+    assert_eq!(niri.columns(1), vec![vec![4, 2], vec![1, 3]]);
+}
+
+#[test]
+fn active_workspace_of_another_output() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]])
+            .workspace(2, "HDMI-A-1", &[&[5], &[6], &[7], &[8]]),
+    );
+
+    niri.run_ok(&["--output", "HDMI-A-1"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1], vec![2], vec![3], vec![4]]);
+    assert_eq!(niri.columns(2), vec![vec![5, 6], vec![7, 8]]);
+    assert_eq!(niri.state().focused_workspace, 1);
+}
+
+#[test]
+fn column_count_has_to_fit_the_windows() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]]));
+
+    for (args, error) in [
+        (&["--columns", "0"][..], "--columns must be at least 1"),
+        (&["--max-rows", "0"], "--max-rows must be at least 1"),
+        (
+            &["--columns", "5"],
+            "Cannot fill 5 columns with only 4 windows",
+        ),
+        (
+            &["--columns", "1", "--max-rows", "3"],
+            "4 windows do not fit into 1 columns of at most 3 rows",
+        ),
+        (
+            &["--columns", "4294967296", "--max-rows", "4294967296"],
+            "Cannot fill 4294967296 columns with only 4 windows",
+        ),
+    ] {
+        let output = niri.run(args);
+
+        assert!(!output.status.success(), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(error), "{:?}: {}", args, stderr);
+    }
+    assert!(niri.state().actions.is_empty());
+}
+
+#[test]
+fn plan_as_json_requests() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]]));
+
+    let stdout = niri.run_ok(&["-q", "plan", "--json"]);
+    // Without plan, --json doesn't send anything either
+    assert_eq!(niri.run_ok(&["-q", "--json"]), stdout);
+
+    let requests: Vec<Request> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(!requests.is_empty());
+    assert!(requests.iter().all(|r| matches!(r, Request::Action(_))));
+    assert!(niri.state().actions.is_empty());
+}
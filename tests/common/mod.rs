@@ -0,0 +1,604 @@
+//! Fake niri socket server for the integration tests.
+//!
+//! It keeps a small model of niri's scrolling layout: every workspace is a list of columns of
+//! window ids, plus its floating windows. Actions sent by `niri-compact` are applied to that
+//! model, so tests can run the real binary against it and look at the columns it ends up with.
+
+// Every test binary uses a different part of this module
+#![allow(dead_code)]
+
+use niri_ipc::{
+    Action, Event, LogicalOutput, Output, Reply, Request, Response, SizeChange, Transform, Window,
+    WindowLayout, Workspace, WorkspaceReferenceArg,
+};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output as ProcessOutput, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct FakeWorkspace {
+    pub id: u64,
+    pub idx: u8,
+    pub name: Option<String>,
+    pub output: String,
+    pub columns: Vec<Vec<u64>>,
+    pub floating: Vec<u64>,
+    pub active_window: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WindowProps {
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+}
+
+/// Everything the fake knows about the session.
+#[derive(Debug, Default)]
+pub struct State {
+    pub workspaces: Vec<FakeWorkspace>,
+    pub focused_workspace: u64,
+    pub props: HashMap<u64, WindowProps>,
+    /// Logical sizes of the outputs.
+    pub outputs: HashMap<String, (u32, u32)>,
+    /// Column widths, keyed by the top window of the column when the width was set.
+    pub widths: HashMap<u64, SizeChange>,
+    pub heights: HashMap<u64, SizeChange>,
+    /// Every action received, in order.
+    pub actions: Vec<Action>,
+    /// Names of actions that should fail, like `"ConsumeWindowIntoColumn"`.
+    pub failing: Vec<String>,
+    subscribers: Vec<Sender<Event>>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        State::default()
+    }
+
+    /// Add a workspace on `output` with the given columns.
+    ///
+    /// Workspaces get their index in the order they're added per output. The first one added is
+    /// focused, and the top window of its first column is the active window.
+    pub fn workspace(mut self, id: u64, output: &str, columns: &[&[u64]]) -> Self {
+        let idx = self
+            .workspaces
+            .iter()
+            .filter(|ws| ws.output == output)
+            .count() as u8
+            + 1;
+        let columns: Vec<Vec<u64>> = columns.iter().map(|c| c.to_vec()).collect();
+        if self.workspaces.is_empty() {
+            self.focused_workspace = id;
+        }
+        self.outputs
+            .entry(output.to_string())
+            .or_insert((1920, 1080));
+        self.workspaces.push(FakeWorkspace {
+            id,
+            idx,
+            name: None,
+            output: output.to_string(),
+            active_window: columns.first().map(|c| c[0]),
+            columns,
+            floating: Vec::new(),
+        });
+        self
+    }
+
+    pub fn named(mut self, id: u64, name: &str) -> Self {
+        self.find_mut(id).name = Some(name.to_string());
+        self
+    }
+
+    pub fn floating(mut self, workspace_id: u64, window_id: u64) -> Self {
+        self.find_mut(workspace_id).floating.push(window_id);
+        self
+    }
+
+    pub fn app(mut self, window_id: u64, app_id: &str) -> Self {
+        self.props.entry(window_id).or_default().app_id = Some(app_id.to_string());
+        self
+    }
+
+    pub fn title(mut self, window_id: u64, title: &str) -> Self {
+        self.props.entry(window_id).or_default().title = Some(title.to_string());
+        self
+    }
+
+    pub fn output_size(mut self, output: &str, width: u32, height: u32) -> Self {
+        self.outputs.insert(output.to_string(), (width, height));
+        self
+    }
+
+    /// Focus `window_id` and its workspace.
+    pub fn focus(mut self, window_id: u64) -> Self {
+        let workspace = self.workspace_of(window_id).expect("unknown window");
+        self.focused_workspace = workspace.id;
+        self.find_mut(self.focused_workspace).active_window = Some(window_id);
+        self
+    }
+
+    /// Make the active window of a workspace `window_id` without focusing it.
+    pub fn active(mut self, workspace_id: u64, window_id: u64) -> Self {
+        self.find_mut(workspace_id).active_window = Some(window_id);
+        self
+    }
+
+    pub fn fail(mut self, action: &str) -> Self {
+        self.failing.push(action.to_string());
+        self
+    }
+
+    pub fn columns(&self, workspace_id: u64) -> Vec<Vec<u64>> {
+        self.find(workspace_id).columns.clone()
+    }
+
+    pub fn floating_windows(&self, workspace_id: u64) -> Vec<u64> {
+        self.find(workspace_id).floating.clone()
+    }
+
+    pub fn focused_window(&self) -> Option<u64> {
+        self.find(self.focused_workspace).active_window
+    }
+
+    /// Number of received actions with the given name.
+    pub fn count(&self, action: &str) -> usize {
+        self.actions.iter().filter(|a| name(a) == action).count()
+    }
+
+    fn find(&self, id: u64) -> &FakeWorkspace {
+        self.workspaces
+            .iter()
+            .find(|ws| ws.id == id)
+            .expect("unknown workspace")
+    }
+
+    fn find_mut(&mut self, id: u64) -> &mut FakeWorkspace {
+        self.workspaces
+            .iter_mut()
+            .find(|ws| ws.id == id)
+            .expect("unknown workspace")
+    }
+
+    fn workspace_of(&self, window_id: u64) -> Option<&FakeWorkspace> {
+        self.workspaces.iter().find(|ws| {
+            ws.floating.contains(&window_id) || ws.columns.iter().any(|c| c.contains(&window_id))
+        })
+    }
+
+    /// Focused workspace and the index of the focused column, if a tiled window is focused.
+    fn focused_column(&mut self) -> (&mut FakeWorkspace, Option<usize>) {
+        let focused = self.focused_workspace;
+        let workspace = self.find_mut(focused);
+        let column = workspace.active_window.and_then(|id| {
+            workspace
+                .columns
+                .iter()
+                .position(|column| column.contains(&id))
+        });
+        (workspace, column)
+    }
+
+    fn windows(&self) -> Vec<Window> {
+        let mut windows = Vec::new();
+        for workspace in &self.workspaces {
+            for (column_idx, column) in workspace.columns.iter().enumerate() {
+                for (tile_idx, &id) in column.iter().enumerate() {
+                    windows.push(self.window(id, workspace, Some((column_idx + 1, tile_idx + 1))));
+                }
+            }
+            for &id in &workspace.floating {
+                windows.push(self.window(id, workspace, None));
+            }
+        }
+        windows
+    }
+
+    fn window(&self, id: u64, workspace: &FakeWorkspace, pos: Option<(usize, usize)>) -> Window {
+        let props = self.props.get(&id).cloned().unwrap_or_default();
+        Window {
+            id,
+            title: props.title,
+            app_id: props.app_id,
+            pid: None,
+            workspace_id: Some(workspace.id),
+            is_focused: workspace.id == self.focused_workspace
+                && workspace.active_window == Some(id),
+            is_floating: pos.is_none(),
+            is_urgent: false,
+            layout: WindowLayout {
+                pos_in_scrolling_layout: pos,
+                tile_size: (800.0, 600.0),
+                window_size: (800, 600),
+                tile_pos_in_workspace_view: None,
+                window_offset_in_tile: (0.0, 0.0),
+            },
+        }
+    }
+
+    fn workspaces_reply(&self) -> Vec<Workspace> {
+        let focused_output = &self.find(self.focused_workspace).output;
+        self.workspaces
+            .iter()
+            .map(|ws| Workspace {
+                id: ws.id,
+                idx: ws.idx,
+                name: ws.name.clone(),
+                output: Some(ws.output.clone()),
+                is_urgent: false,
+                is_active: ws.id == self.focused_workspace || &ws.output != focused_output,
+                is_focused: ws.id == self.focused_workspace,
+                active_window_id: ws.active_window,
+            })
+            .collect()
+    }
+
+    fn outputs_reply(&self) -> HashMap<String, Output> {
+        self.outputs
+            .iter()
+            .map(|(name, &(width, height))| {
+                let output = Output {
+                    name: name.clone(),
+                    make: "Fake".to_string(),
+                    model: "Monitor".to_string(),
+                    serial: None,
+                    physical_size: None,
+                    modes: Vec::new(),
+                    current_mode: None,
+                    vrr_supported: false,
+                    vrr_enabled: false,
+                    logical: Some(LogicalOutput {
+                        x: 0,
+                        y: 0,
+                        width,
+                        height,
+                        scale: 1.0,
+                        transform: Transform::Normal,
+                    }),
+                };
+                (name.clone(), output)
+            })
+            .collect()
+    }
+
+    fn handle(&mut self, request: Request) -> Reply {
+        match request {
+            Request::Windows => Ok(Response::Windows(self.windows())),
+            Request::Workspaces => Ok(Response::Workspaces(self.workspaces_reply())),
+            Request::Outputs => Ok(Response::Outputs(self.outputs_reply())),
+            Request::Action(action) => {
+                self.actions.push(action.clone());
+                if self.failing.contains(&name(&action)) {
+                    return Err(format!("simulated failure of {}", name(&action)));
+                }
+                self.act(action).map(|()| Response::Handled)
+            }
+            other => Err(format!("fake niri doesn't support {:?}", other)),
+        }
+    }
+
+    fn act(&mut self, action: Action) -> Result<(), String> {
+        match action {
+            Action::FocusWindow { id } => {
+                let workspace_id = self
+                    .workspace_of(id)
+                    .map(|ws| ws.id)
+                    .ok_or_else(|| format!("window {} not found", id))?;
+                self.focused_workspace = workspace_id;
+                self.find_mut(workspace_id).active_window = Some(id);
+            }
+            Action::FocusWorkspace { reference } => {
+                let focused_output = self.find(self.focused_workspace).output.clone();
+                let workspace = self
+                    .workspaces
+                    .iter()
+                    .find(|ws| match &reference {
+                        WorkspaceReferenceArg::Id(id) => ws.id == *id,
+                        WorkspaceReferenceArg::Index(idx) => {
+                            ws.idx == *idx && ws.output == focused_output
+                        }
+                        WorkspaceReferenceArg::Name(name) => ws.name.as_ref() == Some(name),
+                    })
+                    .ok_or("workspace not found")?;
+                self.focused_workspace = workspace.id;
+            }
+            Action::ExpelWindowFromColumn {} => {
+                let (workspace, column) = self.focused_column();
+                let (Some(column), Some(id)) = (column, workspace.active_window) else {
+                    return Ok(());
+                };
+                if workspace.columns[column].len() > 1 {
+                    workspace.columns[column].retain(|&w| w != id);
+                    workspace.columns.insert(column + 1, vec![id]);
+                }
+            }
+            Action::MoveColumnToIndex { index } => {
+                let (workspace, column) = self.focused_column();
+                if let Some(column) = column {
+                    let moved = workspace.columns.remove(column);
+                    let index = index.saturating_sub(1).min(workspace.columns.len());
+                    workspace.columns.insert(index, moved);
+                }
+            }
+            Action::FocusColumn { index } => {
+                let (workspace, _) = self.focused_column();
+                if !workspace.columns.is_empty() {
+                    let index = index.saturating_sub(1).min(workspace.columns.len() - 1);
+                    workspace.active_window = Some(workspace.columns[index][0]);
+                }
+            }
+            Action::FocusColumnFirst {} => {
+                let (workspace, _) = self.focused_column();
+                if let Some(column) = workspace.columns.first() {
+                    workspace.active_window = Some(column[0]);
+                }
+            }
+            Action::ConsumeWindowIntoColumn {} => {
+                let (workspace, column) = self.focused_column();
+                if let Some(column) = column.filter(|&c| c + 1 < workspace.columns.len()) {
+                    let consumed = workspace.columns[column + 1].remove(0);
+                    workspace.columns[column].push(consumed);
+                    if workspace.columns[column + 1].is_empty() {
+                        workspace.columns.remove(column + 1);
+                    }
+                }
+            }
+            Action::SetColumnDisplay { .. } => {}
+            Action::SetWindowWidth { id, change } => {
+                let id = id.or(self.focused_window()).ok_or("no window focused")?;
+                let workspace = self.workspace_of(id).ok_or("window not found")?;
+                let top = workspace
+                    .columns
+                    .iter()
+                    .find(|column| column.contains(&id))
+                    .map_or(id, |column| column[0]);
+                self.widths.insert(top, change);
+            }
+            Action::SetWindowHeight { id, change } => {
+                let id = id.or(self.focused_window()).ok_or("no window focused")?;
+                self.heights.insert(id, change);
+            }
+            Action::MoveWindowToTiling { id } => {
+                let id = id.or(self.focused_window()).ok_or("no window focused")?;
+                let workspace_id = self.workspace_of(id).ok_or("window not found")?.id;
+                let (_, column) = self.focused_column();
+                let workspace = self.find_mut(workspace_id);
+                if workspace.floating.contains(&id) {
+                    workspace.floating.retain(|&w| w != id);
+                    // niri puts the new column next to the active one
+                    let index = column.map_or(workspace.columns.len(), |c| c + 1);
+                    workspace.columns.insert(index, vec![id]);
+                }
+            }
+            Action::MoveWindowToFloating { id } => {
+                let id = id.or(self.focused_window()).ok_or("no window focused")?;
+                let workspace_id = self.workspace_of(id).ok_or("window not found")?.id;
+                let workspace = self.find_mut(workspace_id);
+                if !workspace.floating.contains(&id) {
+                    for column in &mut workspace.columns {
+                        column.retain(|&w| w != id);
+                    }
+                    workspace.columns.retain(|column| !column.is_empty());
+                    workspace.floating.push(id);
+                }
+            }
+            other => return Err(format!("fake niri doesn't support {:?}", other)),
+        }
+        Ok(())
+    }
+}
+
+/// Variant name of an action, as it appears in the JSON.
+pub fn name(action: &Action) -> String {
+    let json = serde_json::to_value(action).unwrap();
+    json.as_object().unwrap().keys().next().unwrap().clone()
+}
+
+/// Fake niri listening on a socket in its own temporary directory.
+///
+/// The directory also serves as `XDG_RUNTIME_DIR` and `XDG_CONFIG_HOME` of the commands it runs,
+/// so undo state and config files stay out of the real ones.
+pub struct FakeNiri {
+    dir: PathBuf,
+    socket: PathBuf,
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeNiri {
+    pub fn start(state: State) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "niri-compact-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let socket = dir.join("niri.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let state = Arc::new(Mutex::new(state));
+
+        let server_state = state.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                let state = server_state.clone();
+                std::thread::spawn(move || serve(stream, state));
+            }
+        });
+
+        FakeNiri { dir, socket, state }
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    pub fn columns(&self, workspace_id: u64) -> Vec<Vec<u64>> {
+        self.state().columns(workspace_id)
+    }
+
+    pub fn socket(&self) -> &str {
+        self.socket.to_str().unwrap()
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Write the `niri-compact` config file the commands will read.
+    pub fn write_config(&self, contents: &str) {
+        let dir = self.dir.join("niri-compact");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.toml"), contents).unwrap();
+    }
+
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_niri-compact"));
+        command
+            .args(args)
+            .env("NIRI_SOCKET", &self.socket)
+            .env("XDG_RUNTIME_DIR", &self.dir)
+            .env("XDG_CONFIG_HOME", &self.dir)
+            .stdin(Stdio::null());
+        command
+    }
+
+    /// Run `niri-compact` with `args` against this fake.
+    pub fn run(&self, args: &[&str]) -> ProcessOutput {
+        self.command(args).output().unwrap()
+    }
+
+    /// Run `niri-compact` and panic with its output if it fails.
+    pub fn run_ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "niri-compact {:?} failed\nstdout:\n{}\nstderr:\n{}",
+            args,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// Open a new window in its own column at the end of a workspace and tell subscribers.
+    pub fn open_window(&self, workspace_id: u64, window_id: u64) {
+        let mut state = self.state();
+        let workspace = state.find_mut(workspace_id);
+        workspace.columns.push(vec![window_id]);
+        let column = workspace.columns.len();
+        let workspace = workspace.clone();
+        let window = state.window(window_id, &workspace, Some((column, 1)));
+        state.broadcast(Event::WindowOpenedOrChanged { window });
+    }
+
+    /// Wait until a client subscribed to the event stream, or panic after a few seconds.
+    pub fn wait_for_subscriber(&self) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while self.state().subscribers.is_empty() {
+            assert!(
+                Instant::now() < deadline,
+                "nothing subscribed to the event stream"
+            );
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    /// Wait until `workspace_id` has the expected columns, or panic after a few seconds.
+    pub fn wait_for_columns(&self, workspace_id: u64, expected: &[&[u64]]) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let columns = self.columns(workspace_id);
+            if columns
+                .iter()
+                .map(Vec::as_slice)
+                .eq(expected.iter().copied())
+            {
+                return;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "workspace {} has columns {:?}, expected {:?}",
+                workspace_id,
+                columns,
+                expected
+            );
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+impl Drop for FakeNiri {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+impl State {
+    fn broadcast(&mut self, event: Event) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+fn serve(stream: UnixStream, state: Arc<Mutex<State>>) {
+    let mut writer = stream.try_clone().unwrap();
+    let reader = BufReader::new(stream);
+
+    for line in reader.lines() {
+        let Ok(line) = line else { return };
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                let reply: Reply = Err(format!("error parsing request: {}", err));
+                let _ = writeln!(writer, "{}", serde_json::to_string(&reply).unwrap());
+                continue;
+            }
+        };
+
+        if let Request::EventStream = request {
+            stream_events(writer, &state);
+            return;
+        }
+
+        let reply = state.lock().unwrap().handle(request);
+        if writeln!(writer, "{}", serde_json::to_string(&reply).unwrap()).is_err() {
+            return;
+        }
+    }
+}
+
+/// Answer an `EventStream` request and keep sending events until the client goes away.
+fn stream_events(mut writer: UnixStream, state: &Mutex<State>) {
+    let (sender, receiver) = mpsc::channel();
+    let windows = {
+        let mut state = state.lock().unwrap();
+        state.subscribers.push(sender);
+        state.windows()
+    };
+
+    let reply: Reply = Ok(Response::Handled);
+    let initial = Event::WindowsChanged { windows };
+    for line in [
+        serde_json::to_string(&reply).unwrap(),
+        serde_json::to_string(&initial).unwrap(),
+    ] {
+        if writeln!(writer, "{}", line).is_err() {
+            return;
+        }
+    }
+
+    for event in receiver {
+        if writeln!(writer, "{}", serde_json::to_string(&event).unwrap()).is_err() {
+            return;
+        }
+    }
+}
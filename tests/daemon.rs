@@ -0,0 +1,49 @@
+mod common;
+
+use common::{FakeNiri, State};
+
+#[test]
+fn daemon_arranges_after_a_window_opens() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2]]).workspace(
+        2,
+        "DP-1",
+        &[&[3], &[4]],
+    ));
+
+    let mut daemon = niri
+        .command(&["-q", "daemon", "--watch", "1", "--debounce", "50"])
+        .spawn()
+        .unwrap();
+    niri.wait_for_subscriber();
+
+    niri.open_window(1, 5);
+    niri.open_window(2, 6);
+    niri.wait_for_columns(1, &[&[1, 2], &[5]]);
+
+    daemon.kill().unwrap();
+    daemon.wait().unwrap();
+    // Workspace 2 isn't watched
+    assert_eq!(niri.columns(2), vec![vec![3], vec![4], vec![6]]);
+}
+
+#[test]
+fn daemon_rejects_one_off_options() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2]]));
+
+    let output = niri.run(&["daemon", "--watch", "1", "--all-workspaces"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--all-workspaces can't be used with the daemon"));
+}
+
+#[test]
+fn daemon_checks_the_layout_up_front() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2]]));
+
+    let output = niri.run(&["daemon", "--watch", "1", "-l", "spiral"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unknown layout \"spiral\""));
+}
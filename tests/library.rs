@@ -0,0 +1,131 @@
+mod common;
+
+use common::{FakeNiri, State};
+use niri_compact::cli::ArrangeArgs;
+use niri_compact::layout::{Column, Layout, LayoutInput, Registry};
+use niri_compact::{layout, plan, select, NiriClient};
+use niri_ipc::{Action, ColumnDisplay, SizeChange};
+
+#[test]
+fn arrange_through_the_library() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3]]));
+
+    let mut client = NiriClient::new(niri.socket()).unwrap();
+    niri_compact::arrange(
+        &mut client,
+        &Registry::default(),
+        &ArrangeArgs {
+            layout: Some("master-stack".to_string()),
+            state_dir: Some(niri.dir().to_path_buf()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(niri.columns(1), vec![vec![1], vec![2, 3]]);
+}
+
+#[test]
+fn plan_and_run_step_by_step() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]])
+            .app(3, "firefox"),
+    );
+
+    let mut client = NiriClient::new(niri.socket()).unwrap();
+    let windows = client.get_windows().unwrap();
+    let args = ArrangeArgs {
+        exclude_app: vec!["firefox".parse().unwrap()],
+        ..Default::default()
+    };
+    let ids: Vec<u64> = windows
+        .iter()
+        .filter(|w| select::is_selected(&args, w))
+        .map(|w| w.id)
+        .collect();
+
+    let columns = layout::grid(&ids, 1, Default::default());
+    niri_compact::run_plan(&mut client, plan::plan(&columns, &[]), false).unwrap();
+
+    assert_eq!(niri.columns(1), vec![vec![1, 2, 4], vec![3]]);
+}
+
+/// Every window in one tabbed column, the most recent one on top.
+struct Tabs;
+
+impl Layout for Tabs {
+    fn columns(&self, input: &LayoutInput) -> anyhow::Result<Vec<Column>> {
+        let mut windows = input.window_ids();
+        windows.reverse();
+        Ok(vec![Column {
+            display: ColumnDisplay::Tabbed,
+            ..Column::new(windows, SizeChange::SetProportion(100.0))
+        }])
+    }
+}
+
+/// Leaves out the last window.
+struct Broken;
+
+impl Layout for Broken {
+    fn columns(&self, input: &LayoutInput) -> anyhow::Result<Vec<Column>> {
+        let windows = input.window_ids();
+        Ok(vec![Column::new(
+            windows[..windows.len() - 1].to_vec(),
+            SizeChange::SetProportion(100.0),
+        )])
+    }
+}
+
+#[test]
+fn custom_layout() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3]]));
+
+    let mut registry = Registry::default();
+    registry.register("tabs", Tabs);
+    registry.register("broken", Broken);
+    let mut client = NiriClient::new(niri.socket()).unwrap();
+
+    let args = |name: &str| ArrangeArgs {
+        layout: Some(name.to_string()),
+        state_dir: Some(niri.dir().to_path_buf()),
+        ..Default::default()
+    };
+    niri_compact::arrange(&mut client, &registry, &args("tabs")).unwrap();
+
+    assert_eq!(niri.columns(1), vec![vec![3, 2, 1]]);
+    assert!(niri.state().actions.iter().any(|action| matches!(
+        action,
+        Action::SetColumnDisplay {
+            display: ColumnDisplay::Tabbed
+        }
+    )));
+
+    let err = niri_compact::arrange(&mut client, &registry, &args("broken")).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("has to place windows [1, 2, 3] once each"));
+
+    let err = niri_compact::arrange(&mut client, &registry, &args("spiral")).unwrap_err();
+    assert!(err.to_string().contains("known layouts"));
+}
+
+#[test]
+fn master_stack_without_windows() {
+    let registry = Registry::default();
+    let args = ArrangeArgs::default();
+    let input = LayoutInput {
+        windows: &[],
+        active_window: None,
+        output_size: None,
+        args: &args,
+    };
+
+    let err = registry
+        .get("master-stack")
+        .unwrap()
+        .columns(&input)
+        .unwrap_err();
+    assert!(err.to_string().contains("no windows to arrange"));
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{FakeNiri, State};
+use std::path::Path;
+use std::process::Command;
+
+fn fixture(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Run `niri-compact` without any niri to talk to.
+fn run_offline(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_niri-compact"))
+        .args(args)
+        .env_remove("NIRI_SOCKET")
+        .env("XDG_CONFIG_HOME", env!("CARGO_TARGET_TMPDIR"))
+        .env("XDG_RUNTIME_DIR", env!("CARGO_TARGET_TMPDIR"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn recorded_run_replays() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4], &[5]])
+            .focus(2),
+    );
+    let transcript = niri.dir().join("transcript.jsonl");
+    let transcript = transcript.to_str().unwrap();
+
+    niri.run_ok(&["--columns", "2", "--record", transcript]);
+    let recorded = niri.state().actions.len();
+    let lines = std::fs::read_to_string(transcript).unwrap().lines().count();
+    // Windows and workspaces, then the actions
+    assert_eq!(lines, recorded + 2);
+
+    let output = run_offline(&["--columns", "2", "--replay", transcript]);
+    assert!(output.status.success(), "{:?}", output);
+}
+
+#[test]
+fn replay_fixture() {
+    let output = run_offline(&[
+        "--exclude-app",
+        "firefox",
+        "--replay",
+        &fixture("exclude-firefox.jsonl"),
+    ]);
+
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("arranged 4 windows into 2 columns"));
+}
+
+#[test]
+fn replay_with_other_options_diverges() {
+    let output = run_offline(&["--replay", &fixture("exclude-firefox.jsonl")]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Replay diverged from the transcript"));
+}
+
+#[test]
+fn replay_has_to_use_the_whole_transcript() {
+    let output = run_offline(&[
+        "--exclude-app",
+        "firefox",
+        "--dry-run",
+        "--replay",
+        &fixture("exclude-firefox.jsonl"),
+    ]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("requests of the transcript left"));
+}
@@ -0,0 +1,131 @@
+#![cfg(feature = "scripting")]
+
+mod common;
+
+use common::{FakeNiri, State};
+use niri_ipc::SizeChange;
+
+fn write_layout(niri: &FakeNiri, name: &str, script: &str) {
+    let dir = niri.dir().join("niri-compact").join("layouts");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join(format!("{}.rhai", name)), script).unwrap();
+}
+
+#[test]
+fn script_layout() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]])
+            .focus(3),
+    );
+    write_layout(
+        &niri,
+        "focus-left",
+        r#"
+        // The focused window on the left, the others stacked in pairs
+        fn layout(windows, output) {
+            let focused = windows.filter(|w| w.focused).map(|w| w.id);
+            let others = windows.filter(|w| !w.focused).map(|w| w.id);
+            [#{ windows: focused, width: 50 }, others.extract(0, 2), others.extract(2)]
+        }
+        "#,
+    );
+
+    niri.run_ok(&["--layout", "focus-left"]);
+
+    assert_eq!(niri.columns(1), vec![vec![3], vec![1, 2], vec![4]]);
+    let state = niri.state();
+    assert_eq!(state.widths[&3], SizeChange::SetProportion(50.0));
+    assert_eq!(state.widths[&1], SizeChange::SetProportion(25.0));
+    assert_eq!(state.focused_window(), Some(3));
+}
+
+#[test]
+fn script_sees_window_properties() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3]])
+            .app(2, "firefox")
+            .title(3, "notes"),
+    );
+    write_layout(
+        &niri,
+        "by-app",
+        r#"
+        fn layout(windows, output) {
+            let browsers = windows.filter(|w| w.app_id == "firefox").map(|w| w.id);
+            let rest = windows.filter(|w| w.app_id != "firefox").map(|w| w.id);
+            if output.width < 1000.0 || rest[1] != 3 || windows[2].title != "notes" {
+                throw "unexpected input";
+            }
+            [browsers, rest]
+        }
+        "#,
+    );
+
+    niri.run_ok(&["-l", "by-app"]);
+
+    assert_eq!(niri.columns(1), vec![vec![2], vec![1, 3]]);
+}
+
+#[test]
+fn script_errors_are_reported() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2]]));
+    write_layout(&niri, "lossy", "fn layout(windows, output) { [[1]] }");
+    write_layout(
+        &niri,
+        "typo",
+        "fn layout(windows, output) { [#{ window: [1, 2] }] }",
+    );
+
+    let output = niri.run(&["-l", "lossy"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("has to place windows [1, 2] once each"));
+
+    let output = niri.run(&["-l", "typo"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Column 1 returned by the layout script"));
+
+    for (name, script, error) in [
+        (
+            "negative",
+            "fn layout(windows, output) { [#{ windows: [1], width: -20 }, [2]] }",
+            "Column 1 returned by the layout script",
+        ),
+        (
+            "tall",
+            "fn layout(windows, output) { [[1], #{ windows: [2], heights: [60, 50] }] }",
+            "Column 2 returned by the layout script",
+        ),
+        (
+            "greedy",
+            "fn layout(windows, output) { [#{ windows: [1], width: 100 }, [2]] }",
+            "Columns [2] returned by the layout script have no width",
+        ),
+    ] {
+        write_layout(&niri, name, script);
+        let output = niri.run(&["-l", name]);
+        assert!(!output.status.success(), "{}", name);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(error), "{}: {}", name, stderr);
+    }
+
+    assert!(niri.state().actions.is_empty());
+}
+
+#[test]
+fn broken_script_only_breaks_its_own_layout() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2], &[3], &[4]]));
+    write_layout(&niri, "broken", "fn layout(windows, output) { [");
+
+    let output = niri.run(&["-l", "broken"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Skipping layout \"broken\""));
+    assert!(stderr.contains("Unknown layout \"broken\""));
+
+    niri.run_ok(&[]);
+    assert_eq!(niri.columns(1), vec![vec![1, 2], vec![3, 4]]);
+}
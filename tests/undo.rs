@@ -0,0 +1,49 @@
+mod common;
+
+use common::{FakeNiri, State};
+
+#[test]
+fn undo_restores_the_columns() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1, 2, 3], &[4], &[5]])
+            .focus(4),
+    );
+
+    niri.run_ok(&["--columns", "2"]);
+    assert_eq!(niri.columns(1), vec![vec![1, 2, 3], vec![4, 5]]);
+
+    // Asking for the JSON plan leaves the windows alone
+    let stdout = niri.run_ok(&["undo", "--json"]);
+    assert!(stdout.contains("MoveColumnToIndex"));
+    assert_eq!(niri.columns(1), vec![vec![1, 2, 3], vec![4, 5]]);
+
+    niri.run_ok(&["undo"]);
+    assert_eq!(niri.columns(1), vec![vec![1, 2, 3], vec![4], vec![5]]);
+    assert_eq!(niri.state().focused_window(), Some(4));
+}
+
+#[test]
+fn undo_floats_windows_again() {
+    let niri = FakeNiri::start(
+        State::new()
+            .workspace(1, "DP-1", &[&[1], &[2], &[3]])
+            .floating(1, 4),
+    );
+
+    niri.run_ok(&["--include-floating"]);
+    niri.run_ok(&["undo"]);
+
+    assert_eq!(niri.columns(1), vec![vec![1], vec![2], vec![3]]);
+    assert_eq!(niri.state().floating_windows(1), vec![4]);
+}
+
+#[test]
+fn nothing_to_undo() {
+    let niri = FakeNiri::start(State::new().workspace(1, "DP-1", &[&[1], &[2]]));
+
+    let stdout = niri.run_ok(&["undo"]);
+
+    assert!(stdout.contains("Nothing to undo"));
+    assert!(niri.state().actions.is_empty());
+}